@@ -1,28 +1,152 @@
-use anyhow::Result;
-use clap::Parser;
-use std::fs::File;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    fs::File,
+    io::{BufReader, Write as _},
+};
 
 #[derive(Parser)]
 #[command(name = "broodrep-cli")]
 #[command(about = "A StarCraft 1 replay file parser")]
 #[command(version)]
+#[command(subcommand_precedence_over_arg = true)]
 struct Args {
-    /// Path to the StarCraft 1 replay file (.rep)
-    replay_file: std::path::PathBuf,
+    /// Paths to the StarCraft 1 replay files (.rep)
+    #[arg(required = true)]
+    replay_files: Vec<std::path::PathBuf>,
+
+    /// Output format for the replay information (when no command is given)
+    #[arg(long, value_enum, default_value_t = InfoFormat::Text)]
+    format: InfoFormat,
+
+    /// Include each player's APM in the replay information (requires parsing every command)
+    #[arg(long)]
+    apm: bool,
+
+    /// Include the game's chat log in the replay information (requires parsing every command)
+    #[arg(long)]
+    chat: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the build order of a player, in a format suitable for sharing
+    BuildOrder {
+        /// Name of the player to print the build order for (defaults to all players)
+        #[arg(long)]
+        player: Option<String>,
+        /// Include each player's (approximate) supply when each step was started
+        #[arg(long)]
+        supply: bool,
+        /// Only print the first N steps
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Only print steps started at or before this game time
+        #[arg(long, value_name = "MM:SS", value_parser = parse_game_time)]
+        until: Option<std::time::Duration>,
+    },
+    /// Print every command in the replay
+    Commands {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Write the (decompressed) data of a section to stdout or a file
+    Dump {
+        /// The section to dump: one of header, commands, mapdata, playernames, skin, lmts, bfix,
+        /// cclr, gcfg, or sbat (case-insensitive), or the ID of any other section as a
+        /// little-endian hex number (e.g. 0x54534554 for `TEST`)
+        #[arg(value_parser = parse_section)]
+        section: broodrep::ReplaySection,
+        /// File to write the data to (defaults to stdout)
+        #[arg(long, value_name = "FILE")]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Write the map the game was played on to a standalone CHK file
+    ExtractMap {
+        /// File to write the CHK data to
+        #[arg(long, value_name = "FILE")]
+        out: std::path::PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InfoFormat {
+    /// Human-readable report
+    Text,
+    /// A single JSON object (see `Replay::to_json` for its shape)
+    Json,
 }
 
-fn main() -> Result<()> {
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable output, one command per line
+    Text,
+    /// CSV with a header row (see `Replay::write_commands_csv` for the columns)
+    Csv,
+}
+
+fn main() {
     let args = Args::parse();
 
-    let file = File::open(&args.replay_file)?;
-    let replay = broodrep::Replay::new(file)?;
+    // Separators would break output meant to be read by other programs
+    let machine_readable = match args.command {
+        None => matches!(args.format, InfoFormat::Json),
+        Some(Command::Commands { format }) => matches!(format, OutputFormat::Csv),
+        Some(Command::Dump { .. }) => true,
+        Some(_) => false,
+    };
+    let show_separators = args.replay_files.len() > 1 && !machine_readable;
+
+    let mut failed = false;
+    for (i, path) in args.replay_files.iter().enumerate() {
+        if show_separators {
+            if i > 0 {
+                println!();
+            }
+            println!("==> {} <==", path.display());
+        }
+        if let Err(e) = process_replay(path, &args) {
+            eprintln!("{}: {e:#}", path.display());
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn process_replay(path: &std::path::Path, args: &Args) -> Result<()> {
+    let mut replay = broodrep::Replay::open(path)?;
 
-    display_replay_info(&replay);
+    match args.command {
+        None => match args.format {
+            InfoFormat::Text => display_replay_info(&mut replay, args.apm, args.chat)?,
+            InfoFormat::Json => println!("{}", replay.to_json()?),
+        },
+        Some(Command::BuildOrder {
+            ref player,
+            supply,
+            limit,
+            until,
+        }) => display_build_order(&mut replay, player.as_deref(), supply, limit, until)?,
+        Some(Command::Commands { format }) => display_commands(&mut replay, format)?,
+        Some(Command::Dump { section, ref out }) => dump_section(&mut replay, section, out)?,
+        Some(Command::ExtractMap { ref out }) => extract_map(&mut replay, out)?,
+    }
 
     Ok(())
 }
 
-fn display_replay_info(replay: &broodrep::Replay<std::fs::File>) {
+fn display_replay_info(
+    replay: &mut broodrep::Replay<BufReader<File>>,
+    show_apm: bool,
+    show_chat: bool,
+) -> Result<()> {
     println!("StarCraft 1 Replay Information");
     println!("=============================");
     println!();
@@ -32,8 +156,12 @@ fn display_replay_info(replay: &broodrep::Replay<std::fs::File>) {
     println!("  Format:        {}", replay.format());
     println!("  Engine:        {}", replay.engine());
 
-    let duration = format_duration(replay.frames(), replay.game_speed());
-    println!("  Duration:      {duration}");
+    println!(
+        "  Duration:      {} ({} frames at {})",
+        format_game_time(replay.duration()),
+        replay.frames(),
+        replay.game_speed()
+    );
 
     if let Some(start_time) = replay.start_time() {
         println!(
@@ -55,11 +183,12 @@ fn display_replay_info(replay: &broodrep::Replay<std::fs::File>) {
     println!();
 
     // Players Section
+    let apm = if show_apm { Some(replay.apm()?) } else { None };
     let players: Vec<_> = replay.players().collect();
     if !players.is_empty() {
         println!("Players:");
         for (i, player) in players.iter().enumerate() {
-            println!(
+            print!(
                 "  [{}] {} ({}, {}, Team {})",
                 i + 1,
                 player.name,
@@ -67,6 +196,11 @@ fn display_replay_info(replay: &broodrep::Replay<std::fs::File>) {
                 player.player_type,
                 player.team
             );
+            if let Some(apm) = &apm {
+                let player_apm = apm.get(&player.network_id).copied().unwrap_or(0.0);
+                print!(" - {player_apm:.0} APM");
+            }
+            println!();
         }
         println!();
     }
@@ -80,12 +214,195 @@ fn display_replay_info(replay: &broodrep::Replay<std::fs::File>) {
         }
         println!();
     }
+
+    // Chat Section
+    if show_chat {
+        let names: std::collections::HashMap<_, _> = replay
+            .players()
+            .chain(replay.observers())
+            .map(|p| (p.slot_id, p.name.clone()))
+            .collect();
+        let messages = replay.chat_messages()?;
+        println!("Chat:");
+        if messages.is_empty() {
+            println!("  (no messages)");
+        }
+        for message in messages {
+            let name = names
+                .get(&(message.sender_slot as u16))
+                .map(String::as_str)
+                .unwrap_or("Unknown");
+            println!(
+                "  {} [frame {}] {name}: {}",
+                format_game_time(message.real_time),
+                message.frame,
+                message.message
+            );
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn display_build_order(
+    replay: &mut broodrep::Replay<BufReader<File>>,
+    player: Option<&str>,
+    show_supply: bool,
+    limit: Option<usize>,
+    until: Option<std::time::Duration>,
+) -> Result<()> {
+    let players: Vec<_> = match player {
+        Some(name) => {
+            let player = replay
+                .players()
+                .find(|p| p.name.eq_ignore_ascii_case(name))
+                .with_context(|| format!("no player named {name:?} found in the replay"))?;
+            vec![player.clone()]
+        }
+        None => replay.players().cloned().collect(),
+    };
+    if players.is_empty() {
+        bail!("replay contains no players");
+    }
+
+    let mut build_orders = replay.build_orders()?;
+
+    for (i, player) in players.iter().enumerate() {
+        if players.len() > 1 {
+            if i > 0 {
+                println!();
+            }
+            println!("{} ({}):", player.name, player.race);
+        }
+
+        let steps = build_orders
+            .remove(&player.network_id)
+            .unwrap_or_default()
+            .into_iter()
+            .take_while(|step| until.is_none_or(|until| step.real_time <= until))
+            .take(limit.unwrap_or(usize::MAX));
+        for step in steps {
+            let time = format_game_time(step.real_time);
+            if show_supply {
+                println!("{} - {time} - {}", step.supply, step.name);
+            } else {
+                println!("{time} - {}", step.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn display_commands(
+    replay: &mut broodrep::Replay<BufReader<File>>,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Csv => replay.write_commands_csv(std::io::stdout().lock())?,
+        OutputFormat::Text => {
+            let time_per_step = replay.game_speed().time_per_step();
+            let names: std::collections::HashMap<_, _> = replay
+                .players()
+                .chain(replay.observers())
+                .map(|p| (p.network_id, p.name.clone()))
+                .collect();
+            for command in replay.commands()?.iter() {
+                let name = names
+                    .get(&command.player_id)
+                    .map(String::as_str)
+                    .unwrap_or("Unknown");
+                println!(
+                    "{} - {} - {:?}",
+                    format_game_time(time_per_step * command.frame),
+                    name,
+                    command.kind
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn dump_section(
+    replay: &mut broodrep::Replay<BufReader<File>>,
+    section: broodrep::ReplaySection,
+    out: &Option<std::path::PathBuf>,
+) -> Result<()> {
+    let data = replay
+        .get_raw_section(section)?
+        .with_context(|| format!("replay has no {section:?} section"))?;
+    match out {
+        Some(path) => std::fs::write(path, &data)
+            .with_context(|| format!("failed to write {}", path.display()))?,
+        None => std::io::stdout().lock().write_all(&data)?,
+    }
+    Ok(())
+}
+
+fn extract_map(
+    replay: &mut broodrep::Replay<BufReader<File>>,
+    out: &std::path::Path,
+) -> Result<()> {
+    let data = replay
+        .get_raw_section(broodrep::ReplaySection::MapData)?
+        .context("replay has no map data")?;
+    std::fs::write(out, &data).with_context(|| format!("failed to write {}", out.display()))?;
+
+    let (width, height) = replay.map_dimensions();
+    print!("Wrote {} ({width}x{height}", replay.map_name());
+    // The CHK is written out as-is even if it can't be parsed, since map editors may still be able
+    // to make sense of it
+    if let Some(tileset) = broodrep::MapData::parse(&data)
+        .ok()
+        .and_then(|map| map.tileset())
+    {
+        print!(", {tileset}");
+    }
+    println!(") to {}", out.display());
+    Ok(())
+}
+
+fn format_game_time(time: std::time::Duration) -> String {
+    let total_seconds = time.as_secs();
+    format!("{}:{:02}", total_seconds / 60, total_seconds % 60)
+}
+
+fn parse_section(value: &str) -> Result<broodrep::ReplaySection> {
+    use broodrep::ReplaySection;
+
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        let id = u32::from_str_radix(hex, 16).context("invalid section ID")?;
+        return Ok(broodrep::SectionTag::from(id).into());
+    }
+    Ok(match value.to_ascii_lowercase().as_str() {
+        "header" => ReplaySection::Header,
+        "commands" => ReplaySection::Commands,
+        "mapdata" => ReplaySection::MapData,
+        "playernames" => ReplaySection::PlayerNames,
+        "skin" | "skins" => ReplaySection::Skins,
+        "lmts" | "limits" => ReplaySection::Limits,
+        "bfix" => ReplaySection::Bfix,
+        "cclr" | "customcolors" => ReplaySection::CustomColors,
+        "gcfg" => ReplaySection::Gcfg,
+        "sbat" | "shieldbattery" => ReplaySection::ShieldBattery,
+        _ => bail!("unknown section (use a name or a hex ID like 0x54534554)"),
+    })
 }
 
-fn format_duration(frames: u32, speed: broodrep::GameSpeed) -> String {
-    let total_duration = speed.time_per_step() * frames;
-    let total_seconds = total_duration.as_secs();
-    let minutes = total_seconds / 60;
-    let seconds = total_seconds % 60;
-    format!("{minutes}:{seconds:02} ({frames} frames at {speed})")
+fn parse_game_time(value: &str) -> Result<std::time::Duration> {
+    let (minutes, seconds) = value
+        .split_once(':')
+        .context("game time must be in the format MM:SS")?;
+    let minutes: u64 = minutes.parse().context("invalid minutes")?;
+    let seconds: u64 = seconds.parse().context("invalid seconds")?;
+    if seconds >= 60 {
+        bail!("seconds must be less than 60");
+    }
+    Ok(std::time::Duration::from_secs(minutes * 60 + seconds))
 }
@@ -4,6 +4,7 @@ use std::io::Cursor;
 use tsify::Tsify;
 use uuid::Uuid;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
 
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND_CONTENT: &'static str = r#"
@@ -23,6 +24,20 @@ pub struct DecompressionConfig {
     /// Maximum compression ratio allowed (default: 500:1)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_compression_ratio: Option<f64>,
+
+    /// Maximum bytes to decompress across all sections read from a replay (default: no limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_decompressed_size: Option<u64>,
+
+    /// Maximum size of the replay file itself, checked while reading from a stream (default: 100MB)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+}
+
+impl DecompressionConfig {
+    fn max_file_size(&self) -> u64 {
+        self.max_file_size.unwrap_or(100 * 1024 * 1024)
+    }
 }
 
 impl From<DecompressionConfig> for broodrep::DecompressionConfig {
@@ -30,6 +45,7 @@ impl From<DecompressionConfig> for broodrep::DecompressionConfig {
         broodrep::DecompressionConfig {
             max_decompressed_size: options.max_decompressed_size.unwrap_or(100 * 1024 * 1024),
             max_compression_ratio: options.max_compression_ratio.unwrap_or(500.0),
+            max_total_decompressed_size: options.max_total_decompressed_size,
             // WASM doesn't have support for Instant::now() so we disable this timing check
             max_decompression_time: None,
         }
@@ -85,6 +101,7 @@ pub enum GameSpeed {
     Fast,
     Faster,
     Fastest,
+    Unknown,
 }
 
 impl From<broodrep::GameSpeed> for GameSpeed {
@@ -97,6 +114,7 @@ impl From<broodrep::GameSpeed> for GameSpeed {
             broodrep::GameSpeed::Fast => GameSpeed::Fast,
             broodrep::GameSpeed::Faster => GameSpeed::Faster,
             broodrep::GameSpeed::Fastest => GameSpeed::Fastest,
+            broodrep::GameSpeed::Unknown(_) => GameSpeed::Unknown,
         }
     }
 }
@@ -157,6 +175,7 @@ pub enum PlayerType {
     Open,
     Neutral,
     Closed,
+    Unknown,
 }
 
 impl From<broodrep::PlayerType> for PlayerType {
@@ -171,6 +190,7 @@ impl From<broodrep::PlayerType> for PlayerType {
             broodrep::PlayerType::Open => PlayerType::Open,
             broodrep::PlayerType::Neutral => PlayerType::Neutral,
             broodrep::PlayerType::Closed => PlayerType::Closed,
+            broodrep::PlayerType::Unknown(_) => PlayerType::Unknown,
         }
     }
 }
@@ -186,6 +206,8 @@ pub enum Race {
     Protoss,
     #[serde(rename = "r")]
     Random,
+    #[serde(rename = "u")]
+    Unknown,
 }
 
 impl From<broodrep::Race> for Race {
@@ -195,6 +217,7 @@ impl From<broodrep::Race> for Race {
             broodrep::Race::Terran => Race::Terran,
             broodrep::Race::Protoss => Race::Protoss,
             broodrep::Race::Random => Race::Random,
+            broodrep::Race::Unknown(_) => Race::Unknown,
         }
     }
 }
@@ -233,6 +256,35 @@ impl From<broodrep::Player> for Player {
     }
 }
 
+/// A single step in a player's build order.
+#[derive(Clone, Debug, Tsify, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[tsify(into_wasm_abi)]
+pub struct BuildStep {
+    /// The frame the step was ordered on.
+    pub frame: u32,
+    /// The (real) time the step was ordered at in milliseconds, based on the game speed.
+    pub real_time_ms: f64,
+    /// The type of unit that was built, trained, or morphed into.
+    pub unit_id: u16,
+    /// The name of the unit type, or "Unknown" if it isn't a valid unit type.
+    pub name: String,
+    /// The (approximate) supply the player had when the step was ordered.
+    pub supply: u32,
+}
+
+impl From<broodrep::BuildStep> for BuildStep {
+    fn from(step: broodrep::BuildStep) -> Self {
+        BuildStep {
+            frame: step.frame,
+            real_time_ms: step.real_time.as_secs_f64() * 1000.0,
+            unit_id: step.unit_id,
+            name: step.name.to_string(),
+            supply: step.supply,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Tsify, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[tsify(into_wasm_abi)]
@@ -286,7 +338,7 @@ pub enum ReplaySection {
     Skins,
     /// Unit/sprite limits for the game
     Limits,
-    /// Bug fix(es)? TODO(tec27): Figure out what this actually is :)
+    /// Flags for the game bug fixes that were enabled
     Bfix,
     /// Custom (extended) team color settings
     CustomColors,
@@ -314,14 +366,38 @@ impl From<ReplaySection> for broodrep::ReplaySection {
     }
 }
 
+impl TryFrom<broodrep::ReplaySection> for ReplaySection {
+    /// The ID of the custom section that has no equivalent here.
+    type Error = broodrep::SectionTag;
+
+    fn try_from(section: broodrep::ReplaySection) -> Result<Self, Self::Error> {
+        Ok(match section {
+            broodrep::ReplaySection::Header => ReplaySection::Header,
+            broodrep::ReplaySection::Commands => ReplaySection::Commands,
+            broodrep::ReplaySection::MapData => ReplaySection::MapData,
+            broodrep::ReplaySection::PlayerNames => ReplaySection::PlayerNames,
+            broodrep::ReplaySection::Skins => ReplaySection::Skins,
+            broodrep::ReplaySection::Limits => ReplaySection::Limits,
+            broodrep::ReplaySection::Bfix => ReplaySection::Bfix,
+            broodrep::ReplaySection::CustomColors => ReplaySection::CustomColors,
+            broodrep::ReplaySection::Gcfg => ReplaySection::Gcfg,
+            broodrep::ReplaySection::ShieldBattery => ReplaySection::ShieldBattery,
+            broodrep::ReplaySection::Custom(tag) => return Err(tag),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Tsify, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[tsify(into_wasm_abi)]
 pub struct ShieldBatteryData {
+    pub section_version: u16,
     pub starcraft_exe_build: u32,
     pub shieldbattery_version: String,
     pub team_game_main_players: [u8; 4],
     pub starting_races: [Race; 12],
+    /// Serialized as a UUID string, since a u128 can't be represented as a JS number without
+    /// losing precision
     pub game_id: Uuid,
     pub user_ids: [u32; 8],
     pub game_logic_version: Option<u16>,
@@ -330,6 +406,7 @@ pub struct ShieldBatteryData {
 impl From<broodrep::ShieldBatteryData> for ShieldBatteryData {
     fn from(data: broodrep::ShieldBatteryData) -> Self {
         ShieldBatteryData {
+            section_version: data.section_version,
             starcraft_exe_build: data.starcraft_exe_build,
             shieldbattery_version: data.shieldbattery_version.to_string(),
             team_game_main_players: data.team_game_main_players,
@@ -366,6 +443,12 @@ impl Replay {
         }
     }
 
+    /// Returns how long the game lasted in real time, in milliseconds, based on its game speed.
+    #[wasm_bindgen(js_name = durationMs)]
+    pub fn duration_ms(&self) -> f64 {
+        self.replay.duration().as_secs_f64() * 1000.0
+    }
+
     #[wasm_bindgen(js_name = hostPlayer)]
     pub fn host_player(&self) -> Option<Player> {
         self.replay.host_player().cloned().map(Into::into)
@@ -403,10 +486,48 @@ impl Replay {
     #[wasm_bindgen(js_name = getRawCustomSection)]
     pub fn get_raw_custom_section(&mut self, section_id: u32) -> Result<Option<Vec<u8>>, JsValue> {
         self.replay
-            .get_raw_section(section_id.to_le_bytes().into())
+            .get_raw_section(broodrep::SectionTag::from(section_id).into())
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
+    /// Returns every known section present in the replay, in the order they appear in the file.
+    /// Sections broodrep doesn't recognize are not included, see `customSectionIds` for those.
+    pub fn sections(&self) -> Vec<ReplaySection> {
+        self.replay
+            .sections_ordered()
+            .into_iter()
+            .filter_map(|(section, _)| section.try_into().ok())
+            .collect()
+    }
+
+    /// Returns the IDs (as 32-bit numbers in little-endian format, as accepted by
+    /// `getRawCustomSection`) of every section in the replay that isn't otherwise known, in the
+    /// order they appear in the file.
+    #[wasm_bindgen(js_name = customSectionIds)]
+    pub fn custom_section_ids(&self) -> Vec<u32> {
+        self.replay
+            .custom_section_ids()
+            .into_iter()
+            .map(u32::from_le_bytes)
+            .collect()
+    }
+
+    /// Returns the build order of the player with the specified network ID: every unit they built,
+    /// trained, or morphed into, in the order they were ordered.
+    #[wasm_bindgen(js_name = buildOrder)]
+    pub fn build_order(&mut self, network_id: u8) -> Result<Vec<BuildStep>, JsValue> {
+        let mut orders = self
+            .replay
+            .build_orders()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(orders
+            .remove(&network_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
     /// Returns the parsed ShieldBattery section, or `undefined` if not present in the replay.
     #[wasm_bindgen(js_name = getShieldBatterySection)]
     pub fn get_shieldbattery_section(&mut self) -> Result<Option<ShieldBatteryData>, JsValue> {
@@ -432,7 +553,75 @@ pub fn parse_replay(
     data: Uint8Array,
     options: Option<DecompressionConfig>,
 ) -> Result<Replay, JsValue> {
-    let bytes: Vec<u8> = data.to_vec();
+    parse_replay_bytes(data.to_vec(), options)
+}
+
+/// Parse a StarCraft replay from a ReadableStream of Uint8Array chunks (e.g. from `fetch` or
+/// `Blob.stream()`). The stream will be read to completion before parsing, but it will be canceled
+/// early if the data doesn't start like a replay or grows past `options.maxFileSize`.
+///
+/// # Arguments
+/// * `stream` - A ReadableStream containing the replay file data
+/// * `options` - Optional decompression configuration to customize security limits
+///
+/// # Returns
+/// A Promise that resolves to a Replay object, or rejects if reading the stream or parsing fails.
+#[wasm_bindgen(js_name = parseReplayStream)]
+pub async fn parse_replay_stream(
+    stream: web_sys::ReadableStream,
+    options: Option<DecompressionConfig>,
+) -> Result<Replay, JsValue> {
+    let options = options.unwrap_or_default();
+    let reader: web_sys::ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+    let result = read_replay_stream(&reader, options.max_file_size()).await;
+    if let Err(e) = &result {
+        // Let the source know we don't need the rest of the data
+        let _ = reader.cancel_with_reason(e);
+    }
+    reader.release_lock();
+
+    parse_replay_bytes(result?, Some(options))
+}
+
+async fn read_replay_stream(
+    reader: &web_sys::ReadableStreamDefaultReader,
+    max_file_size: u64,
+) -> Result<Vec<u8>, JsValue> {
+    let mut bytes = Vec::new();
+    let mut format_checked = false;
+    loop {
+        let result: web_sys::ReadableStreamReadResult =
+            JsFuture::from(reader.read()).await?.unchecked_into();
+        if result.get_done().unwrap_or(true) {
+            return Ok(bytes);
+        }
+        let chunk = result.get_value().dyn_into::<Uint8Array>().map_err(|_| {
+            JsValue::from_str("Failed to parse replay: stream chunk was not a Uint8Array")
+        })?;
+
+        let start = bytes.len();
+        let end = start + chunk.length() as usize;
+        if end as u64 > max_file_size {
+            return Err(JsValue::from_str(
+                "Failed to parse replay: file is larger than the maximum file size",
+            ));
+        }
+        bytes.resize(end, 0);
+        chunk.copy_to(&mut bytes[start..]);
+
+        // Reject things that aren't replays without waiting for the rest of the stream
+        if !format_checked && bytes.len() >= broodrep::FORMAT_DETECTION_LEN {
+            broodrep::detect_replay_format(&bytes)
+                .map_err(|e| JsValue::from_str(&format!("Failed to parse replay: {}", e)))?;
+            format_checked = true;
+        }
+    }
+}
+
+fn parse_replay_bytes(
+    bytes: Vec<u8>,
+    options: Option<DecompressionConfig>,
+) -> Result<Replay, JsValue> {
     let cursor = Cursor::new(bytes);
 
     let config = options.unwrap_or_default().into();
@@ -518,6 +707,8 @@ mod tests {
         let options = DecompressionConfig {
             max_decompressed_size: Some(200 * 1024 * 1024), // 200MB
             max_compression_ratio: Some(1000.0),            // Allow higher compression ratios
+            max_total_decompressed_size: None,
+            max_file_size: None,
         };
 
         let result = parse_replay(data, Some(options));
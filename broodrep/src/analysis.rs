@@ -0,0 +1,1465 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::TurnRate;
+use crate::commands::{Command, CommandKind, CommandType, HotkeyAction, LeaveReason, SelectMode};
+use crate::units::{Cost, tech_cost, unit_cost, unit_name, unit_supply, upgrade_cost};
+
+/// Cumulative counts of the units a player has produced, as of a particular frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProductionSample {
+    /// The frame these counts were taken at (inclusive).
+    pub frame: u32,
+    /// The total number of units produced up to [frame](ProductionSample::frame), keyed by unit
+    /// type.
+    pub counts: BTreeMap<u16, u32>,
+}
+
+/// The supply every player starts the game with (for their 4 workers).
+const STARTING_SUPPLY: u32 = 4;
+
+/// Returns how much a player's supply changes when they issue a command, for estimating their
+/// supply over the course of a game. Buildings placed by Zerg Drones free up the Drone's supply,
+/// since the Drone morphs into the building.
+fn supply_change(kind: &CommandKind) -> i32 {
+    match *kind {
+        // Hatchery through Extractor
+        CommandKind::Build { unit_type, .. } if (131..=149).contains(&unit_type) => -1,
+        _ => produced_unit(kind).map_or(0, |unit_type| unit_supply(unit_type) as i32),
+    }
+}
+
+/// Returns the type of unit a command produces (builds, trains, or morphs into), if any.
+pub(crate) fn produced_unit(kind: &CommandKind) -> Option<u16> {
+    match *kind {
+        CommandKind::Build { unit_type, .. }
+        | CommandKind::Train { unit_type }
+        | CommandKind::UnitMorph { unit_type }
+        | CommandKind::BuildingMorph { unit_type } => Some(unit_type),
+        _ => None,
+    }
+}
+
+pub(crate) fn unit_production_timeline(
+    commands: &[Command],
+    total_frames: u32,
+    interval: u32,
+) -> HashMap<u8, Vec<ProductionSample>> {
+    let mut events: HashMap<u8, Vec<(u32, u16)>> = HashMap::new();
+    for command in commands {
+        if let Some(unit_type) = produced_unit(&command.kind) {
+            events
+                .entry(command.player_id)
+                .or_default()
+                .push((command.frame, unit_type));
+        }
+    }
+
+    events
+        .into_iter()
+        .map(|(player_id, events)| {
+            let mut samples = Vec::new();
+            let mut counts = BTreeMap::new();
+            let mut events = events.into_iter().peekable();
+            for frame in sample_frames(total_frames, interval) {
+                while let Some((_, unit_type)) = events.next_if(|&(f, _)| f <= frame) {
+                    *counts.entry(unit_type).or_insert(0) += 1;
+                }
+                samples.push(ProductionSample {
+                    frame,
+                    counts: counts.clone(),
+                });
+            }
+            // Commands after the replay's final frame shouldn't happen, but if they do we still
+            // want them to be counted
+            if let Some(last) = samples.last_mut() {
+                for (_, unit_type) in events {
+                    *last.counts.entry(unit_type).or_insert(0) += 1;
+                }
+            }
+
+            (player_id, samples)
+        })
+        .collect()
+}
+
+/// Returns the frames that timeline samples should be taken at: every `interval` frames, with a
+/// final sample at `total_frames`.
+fn sample_frames(total_frames: u32, interval: u32) -> impl Iterator<Item = u32> {
+    let interval = interval.max(1);
+    let mut next = Some(0u32);
+    std::iter::from_fn(move || {
+        let frame = next?.saturating_add(interval).min(total_frames);
+        next = if frame >= total_frames {
+            None
+        } else {
+            Some(frame)
+        };
+        Some(frame)
+    })
+}
+
+pub(crate) fn selection_sizes(commands: &[Command]) -> HashMap<u8, BTreeMap<usize, u32>> {
+    let mut sizes: HashMap<u8, BTreeMap<usize, u32>> = HashMap::new();
+    for command in commands {
+        if let CommandKind::Select {
+            mode: SelectMode::Replace,
+            ref unit_tags,
+        } = command.kind
+        {
+            *sizes
+                .entry(command.player_id)
+                .or_default()
+                .entry(unit_tags.len())
+                .or_insert(0) += 1;
+        }
+    }
+    sizes
+}
+
+pub(crate) fn commands_by_player(
+    commands: impl IntoIterator<Item = Command>,
+) -> HashMap<u8, Vec<Command>> {
+    let mut grouped: HashMap<u8, Vec<Command>> = HashMap::new();
+    for command in commands {
+        grouped.entry(command.player_id).or_default().push(command);
+    }
+    grouped
+}
+
+pub(crate) fn command_stats_by_player(
+    commands: &[Command],
+) -> HashMap<u8, HashMap<CommandType, usize>> {
+    let mut stats: HashMap<u8, HashMap<CommandType, usize>> = HashMap::new();
+    for command in commands {
+        *stats
+            .entry(command.player_id)
+            .or_default()
+            .entry(command.kind.command_type())
+            .or_insert(0) += 1;
+    }
+    stats
+}
+
+pub(crate) fn command_stats(commands: &[Command]) -> HashMap<CommandType, usize> {
+    let mut stats = HashMap::new();
+    for command in commands {
+        *stats.entry(command.kind.command_type()).or_insert(0) += 1;
+    }
+    stats
+}
+
+pub(crate) fn turn_rate(commands: &[Command]) -> Option<TurnRate> {
+    commands.iter().rev().find_map(|c| match c.kind {
+        CommandKind::SetLatency { turn_rate } => Some(turn_rate),
+        _ => None,
+    })
+}
+
+/// A chat message sent during the game, with the time it was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    /// The frame the message was sent on.
+    pub frame: u32,
+    /// The time the message was sent in "game time", formatted as `M:SS` (or `H:MM:SS` for games
+    /// over an hour). Game time counts 15 frames as a second regardless of the game speed, which is
+    /// how the original game's timer and most older replay tools display times. On Fastest this
+    /// runs about 1.6x faster than real time.
+    pub game_time: String,
+    /// The real time elapsed from the start of the game until the message was sent, based on the
+    /// game speed (see [GameSpeed::time_per_step](crate::GameSpeed::time_per_step)).
+    pub real_time: Duration,
+    /// The slot ID of the player that sent the message (see [Player::slot_id](crate::Player)).
+    pub sender_slot: u8,
+    pub message: String,
+}
+
+/// Number of frames per second of "game time" (i.e. at Normal speed).
+const GAME_TIME_FRAMES_PER_SECOND: u32 = 15;
+
+/// Formats a frame as a "game time" string (see [ChatMessage::game_time]).
+fn format_game_time(frame: u32) -> String {
+    let seconds = frame / GAME_TIME_FRAMES_PER_SECOND;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+pub(crate) fn chat_messages(commands: &[Command], time_per_step: Duration) -> Vec<ChatMessage> {
+    commands
+        .iter()
+        .filter_map(|c| match c.kind {
+            CommandKind::Chat {
+                sender_slot,
+                ref message,
+            } => Some(ChatMessage {
+                frame: c.frame,
+                game_time: format_game_time(c.frame),
+                real_time: time_per_step * c.frame,
+                sender_slot,
+                message: message.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A player leaving the game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LeaveEvent {
+    /// The frame the player left on.
+    pub frame: u32,
+    /// The ID of the player that left, in the command stream.
+    pub player_id: u8,
+    pub reason: LeaveReason,
+}
+
+pub(crate) fn leave_events(commands: &[Command]) -> Vec<LeaveEvent> {
+    commands
+        .iter()
+        .filter_map(|c| match c.kind {
+            CommandKind::LeaveGame { reason } => Some(LeaveEvent {
+                frame: c.frame,
+                player_id: c.player_id,
+                reason,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A sync command, which the game sends periodically so that clients can check they're still in
+/// the same state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncFrame {
+    /// The frame the sync command was executed on.
+    pub frame: u32,
+    /// The ID of the player that sent the sync command, in the command stream.
+    pub player_id: u8,
+    /// The data of the sync command (not including the command ID). This contains the checksum
+    /// and random seed state, but the exact layout varies between versions, so it's left as-is.
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn sync_frames(commands: &[Command]) -> Vec<SyncFrame> {
+    commands
+        .iter()
+        .filter_map(|c| match c.kind {
+            CommandKind::Sync { ref data } => Some(SyncFrame {
+                frame: c.frame,
+                player_id: c.player_id,
+                data: data.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns which of two opposing players won, based on who left first, or [None] if it can't be
+/// determined.
+pub(crate) fn inferred_winner(events: &[LeaveEvent], players: [u8; 2]) -> Option<u8> {
+    let left_frame = |player_id: u8| {
+        events
+            .iter()
+            .find(|e| e.player_id == player_id)
+            .map(|e| e.frame)
+    };
+    match (left_frame(players[0]), left_frame(players[1])) {
+        (Some(_), None) => Some(players[1]),
+        (None, Some(_)) => Some(players[0]),
+        (Some(a), Some(b)) if a < b => Some(players[1]),
+        (Some(a), Some(b)) if b < a => Some(players[0]),
+        // Nobody left (e.g. the replay was saved mid-game), or both left at the same time
+        _ => None,
+    }
+}
+
+/// A single step in a player's build order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildStep {
+    /// The frame the step was ordered on.
+    pub frame: u32,
+    /// The (real) time the step was ordered at, based on the game speed.
+    pub real_time: Duration,
+    /// The type of unit that was built, trained, or morphed into.
+    pub unit_id: u16,
+    /// The name of the unit type, or "Unknown" if it isn't a valid unit type.
+    pub name: &'static str,
+    /// The (approximate) supply the player had when the step was ordered. This starts at 4 and
+    /// counts every unit the player has ordered so far (see [unit_supply](crate::unit_supply)),
+    /// so it doesn't account for units dying or orders that failed or were canceled, and gets less
+    /// accurate as the game goes on.
+    pub supply: u32,
+}
+
+pub(crate) fn build_orders(
+    commands: &[Command],
+    time_per_step: Duration,
+) -> HashMap<u8, Vec<BuildStep>> {
+    let mut orders: HashMap<u8, Vec<BuildStep>> = HashMap::new();
+    let mut supply: HashMap<u8, u32> = HashMap::new();
+    for command in commands {
+        if let Some(unit_id) = produced_unit(&command.kind) {
+            let supply = supply.entry(command.player_id).or_insert(STARTING_SUPPLY);
+            orders
+                .entry(command.player_id)
+                .or_default()
+                .push(BuildStep {
+                    frame: command.frame,
+                    real_time: time_per_step * command.frame,
+                    unit_id,
+                    name: unit_name(unit_id).unwrap_or("Unknown"),
+                    supply: *supply,
+                });
+            *supply = supply.saturating_add_signed(supply_change(&command.kind));
+        }
+    }
+    orders
+}
+
+/// Returns the unit types a player built, trained, or morphed into, in order, up to `limit` steps.
+pub(crate) fn build_order(commands: &[Command], player_id: u8, limit: usize) -> Vec<u16> {
+    commands
+        .iter()
+        .filter(|c| c.player_id == player_id)
+        .filter_map(|c| produced_unit(&c.kind))
+        .take(limit)
+        .collect()
+}
+
+/// Returns how similar two build orders are, from 0.0 (nothing in common) to 1.0 (identical). This
+/// is 1 minus the edit distance between them, normalized by the length of the longer one. Two
+/// empty build orders are considered identical.
+pub(crate) fn build_order_similarity(a: &[u16], b: &[u16]) -> f64 {
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    // Standard Levenshtein distance, keeping only the previous row
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(x != y);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    1.0 - prev[b.len()] as f64 / max_len as f64
+}
+
+/// Statistics about how a player used control groups (hotkeys).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ControlGroupStats {
+    /// The number of times a selection was assigned to a group (e.g. Ctrl+1).
+    pub assigns: usize,
+    /// The number of times a selection was added to a group (e.g. Shift+1).
+    pub adds: usize,
+    /// The number of times a group was selected (e.g. 1).
+    pub selects: usize,
+    /// The number of distinct groups the player assigned, added to, or selected.
+    pub groups_used: usize,
+}
+
+pub(crate) fn control_group_stats(commands: &[Command]) -> HashMap<u8, ControlGroupStats> {
+    // Bitmasks of the groups each player has used
+    let mut groups: HashMap<u8, u16> = HashMap::new();
+    let mut stats: HashMap<u8, ControlGroupStats> = HashMap::new();
+    for command in commands {
+        let CommandKind::Hotkey { action, group } = command.kind else {
+            continue;
+        };
+        // The game only has groups 0-9, so anything else is a corrupt (or crafted) command
+        if group > 9 {
+            continue;
+        }
+        let count: fn(&mut ControlGroupStats) -> &mut usize = match action {
+            HotkeyAction::Assign => |s| &mut s.assigns,
+            HotkeyAction::Add => |s| &mut s.adds,
+            HotkeyAction::Select => |s| &mut s.selects,
+            HotkeyAction::Unknown(_) => continue,
+        };
+        *count(stats.entry(command.player_id).or_default()) += 1;
+        *groups.entry(command.player_id).or_default() |= 1 << group;
+    }
+
+    for (player_id, player_stats) in stats.iter_mut() {
+        player_stats.groups_used = groups.get(player_id).map_or(0, |g| g.count_ones() as usize);
+    }
+    stats
+}
+
+/// Statistics about the gaps between a player's consecutive actions.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ActionCadence {
+    /// The number of actions the player took.
+    pub actions: usize,
+    /// The mean number of frames between consecutive actions.
+    pub mean_gap: f64,
+    /// The standard deviation of the number of frames between consecutive actions.
+    pub std_dev_gap: f64,
+}
+
+pub(crate) fn action_cadence(commands: &[Command]) -> HashMap<u8, ActionCadence> {
+    let mut frames: HashMap<u8, Vec<u32>> = HashMap::new();
+    for command in commands.iter().filter(|c| c.kind.is_action()) {
+        frames
+            .entry(command.player_id)
+            .or_default()
+            .push(command.frame);
+    }
+
+    frames
+        .into_iter()
+        .filter(|(_, frames)| frames.len() >= 2)
+        .map(|(player_id, frames)| {
+            let gaps = frames
+                .windows(2)
+                .map(|w| w[1].saturating_sub(w[0]) as f64)
+                .collect::<Vec<_>>();
+            let mean_gap = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            let variance =
+                gaps.iter().map(|g| (g - mean_gap).powi(2)).sum::<f64>() / gaps.len() as f64;
+            (
+                player_id,
+                ActionCadence {
+                    actions: frames.len(),
+                    mean_gap,
+                    std_dev_gap: variance.sqrt(),
+                },
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn apm(commands: &[Command], game_length: Duration) -> HashMap<u8, f64> {
+    let minutes = game_length.as_secs_f64() / 60.0;
+    if minutes <= 0.0 {
+        return HashMap::new();
+    }
+
+    let mut actions: HashMap<u8, usize> = HashMap::new();
+    for command in commands.iter().filter(|c| c.kind.is_action()) {
+        *actions.entry(command.player_id).or_insert(0) += 1;
+    }
+    actions
+        .into_iter()
+        .map(|(player_id, count)| (player_id, count as f64 / minutes))
+        .collect()
+}
+
+/// Settings for [Replay::eapm](crate::Replay::eapm).
+#[derive(Debug, Clone)]
+pub struct EapmConfig {
+    /// How many frames apart two actions can be for the first to be considered made redundant by
+    /// the second.
+    pub window: u32,
+}
+
+impl Default for EapmConfig {
+    fn default() -> Self {
+        // ~1/3 of a second at Fastest
+        Self { window: 8 }
+    }
+}
+
+/// Returns whether a command changes which units are selected, replacing whatever was previously
+/// selected.
+fn replaces_selection(kind: &CommandKind) -> bool {
+    matches!(
+        kind,
+        CommandKind::Select {
+            mode: SelectMode::Replace,
+            ..
+        } | CommandKind::Hotkey {
+            action: HotkeyAction::Select,
+            ..
+        }
+    )
+}
+
+pub(crate) fn eapm(
+    commands: &[Command],
+    game_length: Duration,
+    config: &EapmConfig,
+) -> HashMap<u8, f64> {
+    let minutes = game_length.as_secs_f64() / 60.0;
+    if minutes <= 0.0 {
+        return HashMap::new();
+    }
+
+    struct PlayerState<'a> {
+        effective: usize,
+        /// The player's previous action, and whether it was counted as effective
+        last: Option<(u32, &'a CommandKind, bool)>,
+    }
+
+    let mut players: HashMap<u8, PlayerState> = HashMap::new();
+    for command in commands.iter().filter(|c| c.kind.is_action()) {
+        let state = players.entry(command.player_id).or_insert(PlayerState {
+            effective: 0,
+            last: None,
+        });
+        let kind = &command.kind;
+        let mut effective = true;
+        if let Some((frame, last_kind, last_effective)) = state.last
+            && command.frame.saturating_sub(frame) <= config.window
+        {
+            // Repeating the previous action (e.g. spamming a hotkey or the same selection). Train
+            // and morph orders are excluded, as repeating them queues up more units
+            if kind == last_kind
+                && !matches!(
+                    kind,
+                    CommandKind::Train { .. } | CommandKind::UnitMorph { .. }
+                )
+            {
+                effective = false;
+            } else if last_effective
+                && matches!(last_kind, CommandKind::Select { .. })
+                && replaces_selection(kind)
+            {
+                // A selection that was replaced before anything was done with it
+                state.effective -= 1;
+            }
+        }
+
+        if effective {
+            state.effective += 1;
+        }
+        state.last = Some((command.frame, kind, effective));
+    }
+
+    players
+        .into_iter()
+        .map(|(player_id, state)| (player_id, state.effective as f64 / minutes))
+        .collect()
+}
+
+/// Settings for [Replay::intensity_score](crate::Replay::intensity_score).
+#[derive(Debug, Clone)]
+pub struct IntensityConfig {
+    /// The size of the windows (in frames) that actions are counted in when measuring how bursty
+    /// the game was.
+    pub window: u32,
+    /// How much burstiness affects the score. At 0.0 it is ignored entirely, at 1.0 a game whose
+    /// action counts per window have a standard deviation equal to their mean will score twice as
+    /// high as one with perfectly even activity.
+    pub burstiness_weight: f64,
+    /// Games shorter than this (in real time) have their score scaled down proportionally, so that
+    /// a short burst of activity before a player leaves doesn't rank above a full game.
+    pub full_length: Duration,
+}
+
+impl Default for IntensityConfig {
+    fn default() -> Self {
+        Self {
+            // ~1 minute of game time at Fastest
+            window: 1440,
+            burstiness_weight: 0.5,
+            full_length: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+pub(crate) fn intensity_score(
+    commands: &[Command],
+    total_frames: u32,
+    time_per_step: Duration,
+    config: &IntensityConfig,
+) -> f64 {
+    let game_length = time_per_step * total_frames;
+    let minutes = game_length.as_secs_f64() / 60.0;
+    if minutes <= 0.0 {
+        return 0.0;
+    }
+
+    let apm = apm(commands, game_length);
+    if apm.is_empty() {
+        return 0.0;
+    }
+    let mean_apm = apm.values().sum::<f64>() / apm.len() as f64;
+
+    let window = config.window.max(1);
+    let mut actions_per_window = vec![0usize; total_frames.div_ceil(window).max(1) as usize];
+    for command in commands.iter().filter(|c| c.kind.is_action()) {
+        let index = ((command.frame / window) as usize).min(actions_per_window.len() - 1);
+        actions_per_window[index] += 1;
+    }
+
+    let mean = actions_per_window.iter().sum::<usize>() as f64 / actions_per_window.len() as f64;
+    let variance = actions_per_window
+        .iter()
+        .map(|&n| (n as f64 - mean).powi(2))
+        .sum::<f64>()
+        / actions_per_window.len() as f64;
+    let burstiness = variance.sqrt() / mean;
+
+    let length_factor = if config.full_length.is_zero() {
+        1.0
+    } else {
+        (game_length.as_secs_f64() / config.full_length.as_secs_f64()).min(1.0)
+    };
+
+    mean_apm * (1.0 + config.burstiness_weight * burstiness) * length_factor
+}
+
+/// Settings for [Replay::resource_spending](crate::Replay::resource_spending).
+#[derive(Debug, Clone)]
+pub struct SpendingConfig {
+    /// How many frames apart each sample in the spending timelines will be.
+    pub interval: u32,
+    /// Whether cancel commands should refund the cost of the order they canceled. Replays don't
+    /// record which order a cancel applied to, so this assumes it was the most recent matching
+    /// order by that player (e.g. the last unit trained for a cancel train command). Canceled
+    /// buildings and addons refund 75% of their cost, everything else is refunded in full.
+    pub refund_cancels: bool,
+}
+
+impl Default for SpendingConfig {
+    fn default() -> Self {
+        Self {
+            // ~1 minute of game time at Fastest
+            interval: 1440,
+            refund_cancels: true,
+        }
+    }
+}
+
+/// The total resources a player had spent as of a particular frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpendingSample {
+    /// The frame this total was taken at (inclusive).
+    pub frame: u32,
+    /// The resources spent up to [frame](SpendingSample::frame).
+    pub spent: Cost,
+}
+
+/// The resources spent by a single player over the course of a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerSpending {
+    /// Cumulative spending, sampled at regular intervals.
+    pub timeline: Vec<SpendingSample>,
+    /// The total resources spent over the whole game.
+    pub total: Cost,
+}
+
+fn is_addon(unit_type: u16) -> bool {
+    matches!(unit_type, 107 | 108 | 115 | 117 | 118 | 120)
+}
+
+fn refund_building(cost: Cost) -> Cost {
+    Cost {
+        minerals: cost.minerals * 3 / 4,
+        gas: cost.gas * 3 / 4,
+    }
+}
+
+/// Orders a player has paid for that could still be canceled, most recent last.
+#[derive(Default)]
+struct PendingOrders {
+    builds: Vec<(u16, Cost)>,
+    trains: Vec<Cost>,
+    /// Unit and building morphs, along with whether the morph was a building.
+    morphs: Vec<(Cost, bool)>,
+    research: Vec<Cost>,
+    upgrades: Vec<(u8, Cost)>,
+    upgrade_levels: HashMap<u8, u8>,
+}
+
+impl PendingOrders {
+    /// Returns the change in spending caused by the specified command, as (spent, refunded).
+    fn apply(&mut self, kind: &CommandKind, refund_cancels: bool) -> (Cost, Cost) {
+        let mut spent = Cost::default();
+        let mut refunded = Cost::default();
+        match *kind {
+            CommandKind::Build { unit_type, .. } => {
+                if let Some(cost) = unit_cost(unit_type) {
+                    self.builds.push((unit_type, cost));
+                    spent = cost;
+                }
+            }
+            CommandKind::Train { unit_type } => {
+                if let Some(cost) = unit_cost(unit_type) {
+                    self.trains.push(cost);
+                    spent = cost;
+                }
+            }
+            CommandKind::UnitMorph { unit_type } | CommandKind::BuildingMorph { unit_type } => {
+                if let Some(cost) = unit_cost(unit_type) {
+                    let is_building = matches!(kind, CommandKind::BuildingMorph { .. });
+                    self.morphs.push((cost, is_building));
+                    spent = cost;
+                }
+            }
+            CommandKind::Research { tech } => {
+                if let Some(cost) = tech_cost(tech) {
+                    self.research.push(cost);
+                    spent = cost;
+                }
+            }
+            CommandKind::Upgrade { upgrade } => {
+                let level = self.upgrade_levels.entry(upgrade).or_insert(0);
+                if let Some(cost) = upgrade_cost(upgrade, level.saturating_add(1)) {
+                    *level += 1;
+                    self.upgrades.push((upgrade, cost));
+                    spent = cost;
+                }
+            }
+            _ if !refund_cancels => {}
+            CommandKind::CancelBuild | CommandKind::CancelAddon => {
+                let addon = matches!(kind, CommandKind::CancelAddon);
+                if let Some(i) = self.builds.iter().rposition(|&(t, _)| is_addon(t) == addon) {
+                    refunded = refund_building(self.builds.remove(i).1);
+                }
+            }
+            CommandKind::CancelTrain { .. } => {
+                if let Some(cost) = self.trains.pop() {
+                    refunded = cost;
+                }
+            }
+            CommandKind::CancelMorph => {
+                if let Some((cost, is_building)) = self.morphs.pop() {
+                    refunded = if is_building {
+                        refund_building(cost)
+                    } else {
+                        cost
+                    };
+                }
+            }
+            CommandKind::CancelResearch => {
+                if let Some(cost) = self.research.pop() {
+                    refunded = cost;
+                }
+            }
+            CommandKind::CancelUpgrade => {
+                if let Some((upgrade, cost)) = self.upgrades.pop() {
+                    if let Some(level) = self.upgrade_levels.get_mut(&upgrade) {
+                        *level = level.saturating_sub(1);
+                    }
+                    refunded = cost;
+                }
+            }
+            _ => {}
+        }
+
+        (spent, refunded)
+    }
+}
+
+pub(crate) fn resource_spending(
+    commands: &[Command],
+    total_frames: u32,
+    config: &SpendingConfig,
+) -> HashMap<u8, PlayerSpending> {
+    let mut pending: HashMap<u8, PendingOrders> = HashMap::new();
+    let mut events: HashMap<u8, Vec<(u32, Cost, Cost)>> = HashMap::new();
+    for command in commands {
+        let (spent, refunded) = pending
+            .entry(command.player_id)
+            .or_default()
+            .apply(&command.kind, config.refund_cancels);
+        if spent != Cost::default() || refunded != Cost::default() {
+            events
+                .entry(command.player_id)
+                .or_default()
+                .push((command.frame, spent, refunded));
+        }
+    }
+
+    events
+        .into_iter()
+        .map(|(player_id, events)| {
+            let mut timeline = Vec::new();
+            let mut total = Cost::default();
+            let apply = |total: &mut Cost, spent: Cost, refunded: Cost| {
+                total.minerals =
+                    (total.minerals + spent.minerals).saturating_sub(refunded.minerals);
+                total.gas = (total.gas + spent.gas).saturating_sub(refunded.gas);
+            };
+            let mut events = events.into_iter().peekable();
+            for frame in sample_frames(total_frames, config.interval) {
+                while let Some((_, spent, refunded)) = events.next_if(|&(f, _, _)| f <= frame) {
+                    apply(&mut total, spent, refunded);
+                }
+                timeline.push(SpendingSample {
+                    frame,
+                    spent: total,
+                });
+            }
+            // As with production, count anything after the final frame in the last sample
+            for (_, spent, refunded) in events {
+                apply(&mut total, spent, refunded);
+            }
+            if let Some(last) = timeline.last_mut() {
+                last.spent = total;
+            }
+
+            (player_id, PlayerSpending { timeline, total })
+        })
+        .collect()
+}
+
+/// A type of anomaly in a replay's command stream that suggests it was edited.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SplicingAnomaly {
+    /// Commands were recorded for an earlier frame than the commands before them. The game only
+    /// ever writes frames in increasing order.
+    FrameWentBackward,
+    /// A player issued commands after they had left the game.
+    CommandAfterLeave { player_id: u8 },
+    /// Commands were recorded after the final frame of the game listed in the header.
+    CommandAfterEnd,
+    /// No player issued any commands for an unusually long stretch of the game, between two
+    /// periods of activity.
+    ActivityGap,
+}
+
+/// A span of frames in a replay that looks like it may have been edited.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SuspiciousSpan {
+    /// The first frame of the span.
+    pub start_frame: u32,
+    /// The last frame of the span (inclusive).
+    pub end_frame: u32,
+    /// The type of anomaly found in this span.
+    pub anomaly: SplicingAnomaly,
+}
+
+/// Periods without any commands longer than this (~3 minutes at Fastest) are considered suspicious.
+const MAX_ACTIVITY_GAP_FRAMES: u32 = 24 * 180;
+
+pub(crate) fn detect_splicing(commands: &[Command], total_frames: u32) -> Vec<SuspiciousSpan> {
+    let mut spans = Vec::new();
+    let mut left_players = HashMap::new();
+    let mut after_leave = HashMap::new();
+    let mut after_end_start = None;
+    let mut max_frame = 0;
+    let mut last_frame = None::<u32>;
+
+    for command in commands {
+        let frame = command.frame;
+        if let Some(last) = last_frame {
+            if frame < last {
+                spans.push(SuspiciousSpan {
+                    start_frame: frame,
+                    end_frame: last,
+                    anomaly: SplicingAnomaly::FrameWentBackward,
+                });
+            } else if frame - last > MAX_ACTIVITY_GAP_FRAMES {
+                spans.push(SuspiciousSpan {
+                    start_frame: last,
+                    end_frame: frame,
+                    anomaly: SplicingAnomaly::ActivityGap,
+                });
+            }
+        }
+        last_frame = Some(frame);
+        max_frame = max_frame.max(frame);
+
+        if frame > total_frames && after_end_start.is_none() {
+            after_end_start = Some(frame);
+        }
+
+        if let Some(&left_frame) = left_players.get(&command.player_id) {
+            // Only report one span per player, covering everything after they left
+            let span = after_leave
+                .entry(command.player_id)
+                .or_insert((left_frame, frame));
+            span.1 = span.1.max(frame);
+        } else if let CommandKind::LeaveGame { .. } = command.kind {
+            left_players.insert(command.player_id, frame);
+        }
+    }
+
+    for (player_id, (start_frame, end_frame)) in after_leave {
+        spans.push(SuspiciousSpan {
+            start_frame,
+            end_frame,
+            anomaly: SplicingAnomaly::CommandAfterLeave { player_id },
+        });
+    }
+    if let Some(start_frame) = after_end_start {
+        spans.push(SuspiciousSpan {
+            start_frame,
+            end_frame: max_frame,
+            anomaly: SplicingAnomaly::CommandAfterEnd,
+        });
+    }
+
+    spans.sort_by_key(|s| (s.start_frame, s.end_frame));
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(frame: u32, player_id: u8, kind: CommandKind) -> Command {
+        Command {
+            frame,
+            player_id,
+            kind,
+        }
+    }
+
+    #[test]
+    fn production_timeline() {
+        let commands = [
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(
+                20,
+                1,
+                CommandKind::RightClick {
+                    x: 0,
+                    y: 0,
+                    target_unit: None,
+                    unit_type: 228,
+                    queued: false,
+                },
+            ),
+            command(100, 0, CommandKind::Train { unit_type: 7 }),
+            command(
+                101,
+                0,
+                CommandKind::Build {
+                    order: 0x1e,
+                    x: 1,
+                    y: 2,
+                    unit_type: 109,
+                },
+            ),
+            command(150, 1, CommandKind::UnitMorph { unit_type: 41 }),
+        ];
+        let timeline = unit_production_timeline(&commands, 250, 100);
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(
+            timeline[&0],
+            [
+                ProductionSample {
+                    frame: 100,
+                    counts: BTreeMap::from([(7, 2)]),
+                },
+                ProductionSample {
+                    frame: 200,
+                    counts: BTreeMap::from([(7, 2), (109, 1)]),
+                },
+                ProductionSample {
+                    frame: 250,
+                    counts: BTreeMap::from([(7, 2), (109, 1)]),
+                },
+            ]
+        );
+        assert_eq!(
+            timeline[&1]
+                .iter()
+                .map(|s| s.counts.clone())
+                .collect::<Vec<_>>(),
+            [
+                BTreeMap::new(),
+                BTreeMap::from([(41, 1)]),
+                BTreeMap::from([(41, 1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn spending() {
+        let commands = [
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(20, 0, CommandKind::Train { unit_type: 7 }),
+            command(30, 0, CommandKind::CancelTrain { unit_tag: 254 }),
+            command(
+                40,
+                0,
+                CommandKind::Build {
+                    order: 0x1e,
+                    x: 1,
+                    y: 2,
+                    unit_type: 111,
+                },
+            ),
+            command(50, 0, CommandKind::CancelBuild),
+            command(110, 0, CommandKind::Upgrade { upgrade: 7 }),
+            command(120, 0, CommandKind::Upgrade { upgrade: 7 }),
+            command(150, 1, CommandKind::Research { tech: 0 }),
+        ];
+        let config = SpendingConfig {
+            interval: 100,
+            refund_cancels: true,
+        };
+        let spending = resource_spending(&commands, 200, &config);
+        assert_eq!(
+            spending[&0].timeline,
+            [
+                SpendingSample {
+                    frame: 100,
+                    spent: Cost {
+                        minerals: 50 + 150 - 112,
+                        gas: 0,
+                    },
+                },
+                SpendingSample {
+                    frame: 200,
+                    spent: Cost {
+                        minerals: 88 + 100 + 175,
+                        gas: 275,
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            spending[&0].total,
+            Cost {
+                minerals: 363,
+                gas: 275,
+            }
+        );
+        assert_eq!(
+            spending[&1].total,
+            Cost {
+                minerals: 100,
+                gas: 100,
+            }
+        );
+
+        let config = SpendingConfig {
+            refund_cancels: false,
+            ..config
+        };
+        let spending = resource_spending(&commands, 200, &config);
+        assert_eq!(
+            spending[&0].total,
+            Cost {
+                minerals: 100 + 150 + 100 + 175,
+                gas: 275,
+            }
+        );
+    }
+
+    #[test]
+    fn splicing() {
+        let commands = [
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(20, 1, CommandKind::Train { unit_type: 64 }),
+            command(
+                500,
+                1,
+                CommandKind::LeaveGame {
+                    reason: LeaveReason::Quit,
+                },
+            ),
+            command(400, 0, CommandKind::Train { unit_type: 7 }),
+            command(600, 1, CommandKind::Train { unit_type: 64 }),
+            command(700, 1, CommandKind::Train { unit_type: 64 }),
+            command(6000, 0, CommandKind::Train { unit_type: 7 }),
+        ];
+        assert_eq!(
+            detect_splicing(&commands, 5000),
+            [
+                SuspiciousSpan {
+                    start_frame: 400,
+                    end_frame: 500,
+                    anomaly: SplicingAnomaly::FrameWentBackward,
+                },
+                SuspiciousSpan {
+                    start_frame: 500,
+                    end_frame: 700,
+                    anomaly: SplicingAnomaly::CommandAfterLeave { player_id: 1 },
+                },
+                SuspiciousSpan {
+                    start_frame: 700,
+                    end_frame: 6000,
+                    anomaly: SplicingAnomaly::ActivityGap,
+                },
+                SuspiciousSpan {
+                    start_frame: 6000,
+                    end_frame: 6000,
+                    anomaly: SplicingAnomaly::CommandAfterEnd,
+                },
+            ]
+        );
+        assert_eq!(detect_splicing(&commands[..2], 5000), []);
+    }
+
+    #[test]
+    fn selections() {
+        let select = |mode, count: u16| CommandKind::Select {
+            mode,
+            unit_tags: (0..count).collect(),
+        };
+        let commands = [
+            command(1, 0, select(SelectMode::Replace, 1)),
+            command(2, 0, select(SelectMode::Replace, 12)),
+            command(3, 0, select(SelectMode::Add, 3)),
+            command(4, 0, select(SelectMode::Replace, 1)),
+            command(5, 1, select(SelectMode::Remove, 1)),
+        ];
+        let sizes = selection_sizes(&commands);
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[&0], BTreeMap::from([(1, 2), (12, 1)]));
+    }
+
+    #[test]
+    fn cadence() {
+        let commands = [
+            command(
+                0,
+                0,
+                CommandKind::Unknown {
+                    id: 0x3c,
+                    data: vec![],
+                },
+            ),
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(20, 0, CommandKind::Train { unit_type: 7 }),
+            command(25, 0, CommandKind::Sync { data: vec![] }),
+            command(40, 0, CommandKind::Train { unit_type: 7 }),
+            command(50, 1, CommandKind::Train { unit_type: 64 }),
+        ];
+        let cadence = action_cadence(&commands);
+        assert_eq!(cadence.len(), 1);
+        assert_eq!(
+            cadence[&0],
+            ActionCadence {
+                actions: 3,
+                mean_gap: 15.0,
+                std_dev_gap: 5.0,
+            }
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let commands = [
+            command(1, 0, CommandKind::Train { unit_type: 7 }),
+            command(2, 0, CommandKind::Train { unit_type: 64 }),
+            command(3, 0, CommandKind::Sync { data: vec![] }),
+            command(
+                4,
+                0,
+                CommandKind::Unknown {
+                    id: 0x5c,
+                    data: vec![],
+                },
+            ),
+            command(5, 1, CommandKind::Sync { data: vec![] }),
+        ];
+        let stats = command_stats_by_player(&commands);
+        assert_eq!(
+            stats[&0],
+            HashMap::from([
+                (CommandType::Train, 2),
+                (CommandType::Sync, 1),
+                (CommandType::Unknown(0x5c), 1),
+            ])
+        );
+        assert_eq!(stats[&1], HashMap::from([(CommandType::Sync, 1)]));
+    }
+
+    #[test]
+    fn build_order_similarity() {
+        let commands = vec![
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(20, 1, CommandKind::Train { unit_type: 41 }),
+            command(
+                30,
+                0,
+                CommandKind::Select {
+                    mode: SelectMode::Replace,
+                    unit_tags: vec![1],
+                },
+            ),
+            command(
+                40,
+                0,
+                CommandKind::Build {
+                    order: 0x1e,
+                    x: 1,
+                    y: 2,
+                    unit_type: 111,
+                },
+            ),
+            command(50, 0, CommandKind::Train { unit_type: 7 }),
+        ];
+        assert_eq!(build_order(&commands, 0, 10), [7, 111, 7]);
+        assert_eq!(build_order(&commands, 0, 2), [7, 111]);
+
+        assert_eq!(super::build_order_similarity(&[], &[]), 1.0);
+        assert_eq!(super::build_order_similarity(&[7, 111], &[]), 0.0);
+        assert_eq!(
+            super::build_order_similarity(&[7, 111, 7], &[7, 111, 7]),
+            1.0
+        );
+        assert_eq!(
+            super::build_order_similarity(&[7, 7, 111, 7], &[7, 111, 7]),
+            0.75
+        );
+        assert_eq!(super::build_order_similarity(&[7, 109], &[7, 111]), 0.5);
+    }
+
+    #[test]
+    fn chat() {
+        let commands = vec![
+            command(30, 0, CommandKind::Train { unit_type: 7 }),
+            command(
+                1335,
+                0,
+                CommandKind::Chat {
+                    sender_slot: 1,
+                    message: "glhf".into(),
+                },
+            ),
+            command(
+                54000,
+                0,
+                CommandKind::Chat {
+                    sender_slot: 0,
+                    message: "gg".into(),
+                },
+            ),
+        ];
+        assert_eq!(
+            chat_messages(&commands, Duration::from_millis(42)),
+            [
+                ChatMessage {
+                    frame: 1335,
+                    game_time: "1:29".into(),
+                    real_time: Duration::from_millis(56070),
+                    sender_slot: 1,
+                    message: "glhf".into(),
+                },
+                ChatMessage {
+                    frame: 54000,
+                    game_time: "1:00:00".into(),
+                    real_time: Duration::from_millis(2268000),
+                    sender_slot: 0,
+                    message: "gg".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn intensity() {
+        let step = Duration::from_millis(42);
+        let even = (0..100)
+            .map(|i| command(i * 60, (i % 2) as u8, CommandKind::Train { unit_type: 7 }))
+            .collect::<Vec<_>>();
+        let config = IntensityConfig {
+            window: 600,
+            burstiness_weight: 0.5,
+            full_length: Duration::ZERO,
+        };
+        // 50 actions each over 4.2 minutes, evenly spread over the 10 windows
+        let score = intensity_score(&even, 6000, step, &config);
+        assert!((score - 50.0 / 4.2).abs() < 1e-9, "{score}");
+
+        // Same number of actions, but all in the first window
+        let bursty = (0..100)
+            .map(|i| command(i * 6, (i % 2) as u8, CommandKind::Train { unit_type: 7 }))
+            .collect::<Vec<_>>();
+        assert!(intensity_score(&bursty, 6000, step, &config) > score);
+
+        let config = IntensityConfig {
+            full_length: Duration::from_secs(504),
+            ..config
+        };
+        let halved = intensity_score(&even, 6000, step, &config);
+        assert!((halved - score / 2.0).abs() < 1e-9, "{halved}");
+
+        assert_eq!(intensity_score(&[], 6000, step, &config), 0.0);
+        assert_eq!(intensity_score(&even, 0, step, &config), 0.0);
+    }
+
+    #[test]
+    fn actions_per_minute() {
+        let commands = vec![
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(20, 0, CommandKind::Sync { data: vec![] }),
+            command(
+                30,
+                1,
+                CommandKind::Hotkey {
+                    action: crate::HotkeyAction::Assign,
+                    group: 1,
+                },
+            ),
+            command(40, 1, CommandKind::CancelTrain { unit_tag: 1 }),
+            command(
+                50,
+                2,
+                CommandKind::Chat {
+                    sender_slot: 2,
+                    message: "hi".into(),
+                },
+            ),
+        ];
+        let apm = apm(&commands, Duration::from_secs(30));
+        assert_eq!(apm, HashMap::from([(0, 2.0), (1, 4.0)]));
+        assert!(super::apm(&commands, Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn effective_apm() {
+        let select = |tags: &[u16]| CommandKind::Select {
+            mode: SelectMode::Replace,
+            unit_tags: tags.to_vec(),
+        };
+        let hotkey = CommandKind::Hotkey {
+            action: HotkeyAction::Select,
+            group: 1,
+        };
+        let commands = vec![
+            // Reselected before doing anything, only the second counts
+            command(0, 0, select(&[1])),
+            command(4, 0, select(&[2])),
+            // Hotkey spam, only the first counts
+            command(100, 0, hotkey.clone()),
+            command(102, 0, hotkey.clone()),
+            command(104, 0, hotkey.clone()),
+            // Queueing units is effective
+            command(200, 0, CommandKind::Train { unit_type: 7 }),
+            command(201, 0, CommandKind::Train { unit_type: 7 }),
+            // Far enough apart to both count
+            command(300, 1, select(&[3])),
+            command(320, 1, select(&[3])),
+            // The repeated selection is dropped, the original stays
+            command(400, 1, select(&[4])),
+            command(402, 1, select(&[4])),
+        ];
+        let eapm = eapm(&commands, Duration::from_secs(60), &EapmConfig::default());
+        assert_eq!(eapm, HashMap::from([(0, 4.0), (1, 3.0)]));
+    }
+
+    #[test]
+    fn winner() {
+        let leave = |frame, player_id| LeaveEvent {
+            frame,
+            player_id,
+            reason: LeaveReason::Quit,
+        };
+        assert_eq!(inferred_winner(&[], [0, 1]), None);
+        assert_eq!(inferred_winner(&[leave(100, 1)], [0, 1]), Some(0));
+        assert_eq!(inferred_winner(&[leave(100, 0)], [0, 1]), Some(1));
+        assert_eq!(
+            inferred_winner(&[leave(100, 1), leave(150, 0)], [0, 1]),
+            Some(0)
+        );
+        assert_eq!(
+            inferred_winner(&[leave(100, 1), leave(100, 0)], [0, 1]),
+            None
+        );
+        // Observers leaving don't matter
+        assert_eq!(
+            inferred_winner(&[leave(50, 2), leave(100, 0)], [0, 1]),
+            Some(1)
+        );
+
+        let commands = [
+            command(10, 0, CommandKind::Train { unit_type: 7 }),
+            command(
+                20,
+                1,
+                CommandKind::LeaveGame {
+                    reason: LeaveReason::Dropped,
+                },
+            ),
+        ];
+        assert_eq!(
+            leave_events(&commands),
+            [LeaveEvent {
+                frame: 20,
+                player_id: 1,
+                reason: LeaveReason::Dropped,
+            }]
+        );
+    }
+
+    #[test]
+    fn build_steps() {
+        let commands = [
+            command(100, 0, CommandKind::Train { unit_type: 7 }),
+            command(
+                200,
+                0,
+                CommandKind::Build {
+                    order: 0x1e,
+                    x: 1,
+                    y: 2,
+                    unit_type: 109,
+                },
+            ),
+            command(300, 1, CommandKind::UnitMorph { unit_type: 37 }),
+            command(400, 1, CommandKind::Train { unit_type: 500 }),
+        ];
+        let orders = build_orders(&commands, Duration::from_millis(42));
+        assert_eq!(
+            orders[&0],
+            [
+                BuildStep {
+                    frame: 100,
+                    real_time: Duration::from_millis(4200),
+                    unit_id: 7,
+                    name: "SCV",
+                    supply: 4,
+                },
+                BuildStep {
+                    frame: 200,
+                    real_time: Duration::from_millis(8400),
+                    unit_id: 109,
+                    name: "Supply Depot",
+                    supply: 5,
+                },
+            ]
+        );
+        assert_eq!(
+            orders[&1].iter().map(|s| s.name).collect::<Vec<_>>(),
+            ["Zergling", "Unknown"]
+        );
+    }
+
+    #[test]
+    fn build_step_supply() {
+        let build = |unit_type| CommandKind::Build {
+            order: 0x19,
+            x: 1,
+            y: 2,
+            unit_type,
+        };
+        let commands = [
+            // Drone, Drone, Overlord
+            command(10, 0, CommandKind::UnitMorph { unit_type: 41 }),
+            command(20, 0, CommandKind::UnitMorph { unit_type: 41 }),
+            command(30, 0, CommandKind::UnitMorph { unit_type: 42 }),
+            // Spawning Pool and Hatchery each use up a Drone
+            command(40, 0, build(142)),
+            command(50, 0, build(131)),
+            // Lair is morphed from a Hatchery, so no Drone is lost
+            command(60, 0, CommandKind::BuildingMorph { unit_type: 132 }),
+            // Zerglings (a pair uses 1 supply)
+            command(70, 0, CommandKind::UnitMorph { unit_type: 37 }),
+            command(80, 0, CommandKind::UnitMorph { unit_type: 41 }),
+            // Other commands don't affect supply
+            command(85, 0, CommandKind::CancelTrain { unit_tag: 0 }),
+            // Other players are tracked separately, and Terran buildings don't cost supply
+            command(90, 1, build(109)),
+            command(100, 1, CommandKind::Train { unit_type: 7 }),
+            command(110, 1, CommandKind::Train { unit_type: 7 }),
+        ];
+        let orders = build_orders(&commands, Duration::from_millis(42));
+        assert_eq!(
+            orders[&0].iter().map(|s| s.supply).collect::<Vec<_>>(),
+            [4, 5, 6, 6, 5, 4, 4, 5]
+        );
+        assert_eq!(
+            orders[&1].iter().map(|s| s.supply).collect::<Vec<_>>(),
+            [4, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Helpers for parsing many replays at once.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator as _, ParallelIterator as _};
+
+use crate::{BroodrepError, Replay};
+
+/// The path of a replay file, along with the result of parsing it.
+pub type BatchItem = (PathBuf, Result<Replay<BufReader<File>>, BroodrepError>);
+
+/// Returns the paths of every replay (any file with a `.rep` extension, ignoring case) directly
+/// inside `dir`, sorted. Subdirectories are not searched.
+///
+/// This is what [parse_dir] and `par_parse_dir` parse, and can be used to parse the replays some
+/// other way, e.g. by calling [Replay::open] for each path from a thread pool.
+pub fn replay_paths<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, BroodrepError> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_replay = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("rep"));
+        if is_replay && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parses every replay found by [replay_paths], in order of their paths.
+///
+/// Each replay is parsed on the calling thread as the iterator is advanced, and a replay that
+/// fails to parse only produces an error for that item, so one bad file won't stop the rest from
+/// being parsed. An error is only returned up front if the directory itself can't be read.
+pub fn parse_dir<P: AsRef<Path>>(dir: P) -> Result<impl Iterator<Item = BatchItem>, BroodrepError> {
+    Ok(replay_paths(dir)?.into_iter().map(|path| {
+        let replay = Replay::open(&path);
+        (path, replay)
+    }))
+}
+
+/// Like [parse_dir], but returns a parallel iterator that parses the replays on rayon's thread
+/// pool. Collecting it (e.g. into a `Vec`) keeps the results in the same order as [parse_dir].
+///
+/// Requires the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub fn par_parse_dir<P: AsRef<Path>>(
+    dir: P,
+) -> Result<impl IndexedParallelIterator<Item = BatchItem>, BroodrepError> {
+    Ok(replay_paths(dir)?.into_par_iter().map(|path| {
+        let replay = Replay::open(&path);
+        (path, replay)
+    }))
+}
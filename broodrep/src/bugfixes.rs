@@ -0,0 +1,58 @@
+use crate::{BroodrepError, SIZE_BFIX};
+
+/// The contents of a replay's [Bfix](crate::ReplaySection::Bfix) section, which appears to be a
+/// set of flags for the game bug fixes and behavior changes that were enabled when the game was
+/// played (these have changed between SC:R patches, which explains some differences in how
+/// replays from different patch levels play back).
+///
+/// NOTE: None of the individual bits have been identified yet, so only the raw value is available
+/// for now. Accessors for specific fixes should be added here as they're worked out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BugFixFlags(u64);
+
+impl BugFixFlags {
+    /// Parses the (decompressed) contents of a Bfix section.
+    pub fn parse(data: &[u8]) -> Result<BugFixFlags, BroodrepError> {
+        let data: [u8; SIZE_BFIX] = data
+            .try_into()
+            .map_err(|_| BroodrepError::MalformedSection("invalid Bfix section size"))?;
+        Ok(BugFixFlags(u64::from_le_bytes(data)))
+    }
+
+    /// Returns all of the flags, as a little-endian number (so the first byte of the section is
+    /// bits 0-7).
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether the flag at `bit` (0-63, see [BugFixFlags::raw]) is set.
+    pub fn is_set(&self, bit: u32) -> bool {
+        bit < u64::BITS && self.0 & (1 << bit) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let flags = BugFixFlags::parse(&[0x05, 0, 0, 0, 0, 0, 0, 0x80]).unwrap();
+        assert_eq!(flags.raw(), 0x8000_0000_0000_0005);
+        assert!(flags.is_set(0));
+        assert!(!flags.is_set(1));
+        assert!(flags.is_set(2));
+        assert!(flags.is_set(63));
+        assert!(!flags.is_set(64));
+
+        assert!(matches!(
+            BugFixFlags::parse(&[0; 7]),
+            Err(BroodrepError::MalformedSection(_))
+        ));
+        assert!(matches!(
+            BugFixFlags::parse(&[0; 9]),
+            Err(BroodrepError::MalformedSection(_))
+        ));
+    }
+}
@@ -0,0 +1,1064 @@
+use std::{collections::HashMap, fmt};
+
+use byteorder::{LittleEndian as LE, ReadBytesExt as _};
+use thiserror::Error;
+
+use crate::TurnRate;
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("command frame at offset {offset} is truncated")]
+    TruncatedFrame { offset: usize },
+    #[error("command 0x{id:02x} at offset {offset} (frame {frame}) doesn't fit in its frame")]
+    LengthMismatch { frame: u32, offset: usize, id: u8 },
+    #[error("command 0x{id:02x} at offset {offset} (frame {frame}) has an unknown length")]
+    UnknownLength { frame: u32, offset: usize, id: u8 },
+}
+
+/// Settings that control how the command stream is parsed.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CommandParseConfig {
+    /// Whether commands that can't be cleanly parsed should be treated as errors. By default, if a
+    /// command's length is unknown or extends past the end of its frame, the rest of that frame
+    /// is returned as a [CommandKind::Unknown] command and parsing continues with the next frame.
+    /// When `strict` is set, these will instead cause parsing to fail with
+    /// [CommandError::UnknownLength] or [CommandError::LengthMismatch], which is useful for
+    /// validating that a replay is intact.
+    pub strict: bool,
+}
+
+/// A single command issued by a player during the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// The game frame the command was executed on.
+    pub frame: u32,
+    /// The ID of the player that issued the command. This corresponds to
+    /// [Player::network_id](crate::Player::network_id) in the replay header.
+    pub player_id: u8,
+    pub kind: CommandKind,
+}
+
+/// How a [CommandKind::Select] command changes the player's current selection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SelectMode {
+    /// The selection is replaced with the specified units.
+    Replace,
+    /// The specified units are added to the selection (e.g. shift-clicking).
+    Add,
+    /// The specified units are removed from the selection.
+    Remove,
+}
+
+/// What a [CommandKind::Hotkey] command does with its control group.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    /// The current selection is assigned to the group (e.g. Ctrl+1).
+    Assign,
+    /// The units in the group are selected (e.g. 1).
+    Select,
+    /// The current selection is added to the group (e.g. Shift+1).
+    Add,
+    Unknown(u8),
+}
+
+impl From<u8> for HotkeyAction {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => HotkeyAction::Assign,
+            1 => HotkeyAction::Select,
+            2 => HotkeyAction::Add,
+            other => HotkeyAction::Unknown(other),
+        }
+    }
+}
+
+/// Why a player left the game, as recorded in a [CommandKind::LeaveGame] command.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LeaveReason {
+    /// The player chose to leave (e.g. by surrendering or quitting from the menu).
+    Quit,
+    /// The player was disconnected.
+    Dropped,
+    Unknown(u8),
+}
+
+impl From<u8> for LeaveReason {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => LeaveReason::Quit,
+            6 => LeaveReason::Dropped,
+            other => LeaveReason::Unknown(other),
+        }
+    }
+}
+
+impl From<LeaveReason> for u8 {
+    fn from(value: LeaveReason) -> Self {
+        match value {
+            LeaveReason::Quit => 1,
+            LeaveReason::Dropped => 6,
+            LeaveReason::Unknown(other) => other,
+        }
+    }
+}
+
+/// The type (and associated data) of a [Command].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// Changes the player's selected units.
+    Select {
+        mode: SelectMode,
+        /// The tags of the units affected.
+        unit_tags: Vec<u16>,
+    },
+    /// A right click, either on the ground or on a target unit.
+    RightClick {
+        /// Target x position (in pixels).
+        x: u16,
+        /// Target y position (in pixels).
+        y: u16,
+        /// The tag of the unit that was clicked on, or [None] if the ground was clicked.
+        target_unit: Option<u16>,
+        /// The type of the unit that was clicked on ([UNIT_NONE](crate::UNIT_NONE) if the ground was
+        /// clicked).
+        unit_type: u16,
+        /// Whether the order was queued (shift-clicked).
+        queued: bool,
+    },
+    /// Places a building.
+    Build {
+        /// The order used to place the building (e.g. normal construction, Zerg drone morphing,
+        /// or landing a lifted Terran building).
+        order: u8,
+        /// X position of the building (in tiles).
+        x: u16,
+        /// Y position of the building (in tiles).
+        y: u16,
+        unit_type: u16,
+    },
+    /// Trains a unit from a production building.
+    Train { unit_type: u16 },
+    /// Morphs a unit into another unit (e.g. larva into a zergling, hydralisk into a lurker).
+    UnitMorph { unit_type: u16 },
+    /// Morphs a building into another building (e.g. hatchery into lair).
+    BuildingMorph { unit_type: u16 },
+    /// Starts researching a tech (e.g. Stim Packs).
+    Research { tech: u8 },
+    /// Starts researching an upgrade (e.g. Terran Infantry Weapons).
+    Upgrade { upgrade: u8 },
+    /// Cancels the construction of a building.
+    CancelBuild,
+    /// Cancels a unit or building morph.
+    CancelMorph,
+    /// Cancels training a unit. `unit_tag` identifies the queue entry that was canceled (254 is the
+    /// last entry).
+    CancelTrain { unit_tag: u16 },
+    /// Cancels the construction of an addon.
+    CancelAddon,
+    /// Cancels a tech that was being researched.
+    CancelResearch,
+    /// Cancels an upgrade that was being researched.
+    CancelUpgrade,
+    /// A chat message sent during the game.
+    Chat {
+        /// The slot ID of the player that sent the message. Note that the command itself will
+        /// generally be issued by the first player, regardless of who sent the message.
+        sender_slot: u8,
+        message: String,
+    },
+    /// The network latency setting was changed (e.g. with the `/latency` chat command).
+    SetLatency { turn_rate: TurnRate },
+    /// A control group was assigned, selected, or added to. `group` is the group's number as
+    /// displayed in game (0-9).
+    Hotkey { action: HotkeyAction, group: u8 },
+    /// The player left the game.
+    LeaveGame { reason: LeaveReason },
+    /// A sync command, which the game sends periodically so that clients can check they're still
+    /// in the same state. `data` contains the checksum and random seed state, but its exact layout
+    /// varies between versions, so it's left as-is.
+    Sync { data: Vec<u8> },
+    /// A command decoded by an entry in a [CommandDecoderRegistry]. Decode functions are free to
+    /// return other variants instead, this is just a convenient container for command types that
+    /// broodrep has no representation for.
+    Custom { id: u8, data: Vec<u8> },
+    /// A command that broodrep doesn't decode. If the command's length isn't known, `data` will
+    /// contain the remainder of the frame's command block.
+    Unknown { id: u8, data: Vec<u8> },
+}
+
+/// The type of a [CommandKind], without any of its associated data. Useful for grouping commands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CommandType {
+    Select,
+    RightClick,
+    Build,
+    Train,
+    UnitMorph,
+    BuildingMorph,
+    Research,
+    Upgrade,
+    CancelBuild,
+    CancelMorph,
+    CancelTrain,
+    CancelAddon,
+    CancelResearch,
+    CancelUpgrade,
+    Hotkey,
+    Chat,
+    SetLatency,
+    LeaveGame,
+    Sync,
+    /// A [CommandKind::Custom] command with the specified ID.
+    Custom(u8),
+    /// A [CommandKind::Unknown] command with the specified ID.
+    Unknown(u8),
+}
+
+impl fmt::Display for CommandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandType::Custom(id) => write!(f, "Custom(0x{id:02x})"),
+            CommandType::Unknown(id) => write!(f, "Unknown(0x{id:02x})"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+impl CommandKind {
+    /// Returns the [CommandType] of this command.
+    pub fn command_type(&self) -> CommandType {
+        match *self {
+            CommandKind::Select { .. } => CommandType::Select,
+            CommandKind::RightClick { .. } => CommandType::RightClick,
+            CommandKind::Build { .. } => CommandType::Build,
+            CommandKind::Train { .. } => CommandType::Train,
+            CommandKind::UnitMorph { .. } => CommandType::UnitMorph,
+            CommandKind::BuildingMorph { .. } => CommandType::BuildingMorph,
+            CommandKind::Research { .. } => CommandType::Research,
+            CommandKind::Upgrade { .. } => CommandType::Upgrade,
+            CommandKind::CancelBuild => CommandType::CancelBuild,
+            CommandKind::CancelMorph => CommandType::CancelMorph,
+            CommandKind::CancelTrain { .. } => CommandType::CancelTrain,
+            CommandKind::CancelAddon => CommandType::CancelAddon,
+            CommandKind::CancelResearch => CommandType::CancelResearch,
+            CommandKind::CancelUpgrade => CommandType::CancelUpgrade,
+            CommandKind::Hotkey { .. } => CommandType::Hotkey,
+            CommandKind::Chat { .. } => CommandType::Chat,
+            CommandKind::SetLatency { .. } => CommandType::SetLatency,
+            CommandKind::LeaveGame { .. } => CommandType::LeaveGame,
+            CommandKind::Sync { .. } => CommandType::Sync,
+            CommandKind::Custom { id, .. } => CommandType::Custom(id),
+            CommandKind::Unknown { id, .. } => CommandType::Unknown(id),
+        }
+    }
+
+    /// Returns whether this command was a player action, as opposed to a command the game issues
+    /// to manage the game itself (e.g. syncing, lobby changes, leaving), or a chat message.
+    /// Commands decoded by a [CommandDecoderRegistry] are always considered actions.
+    pub fn is_action(&self) -> bool {
+        match self {
+            CommandKind::Chat { .. }
+            | CommandKind::SetLatency { .. }
+            | CommandKind::LeaveGame { .. }
+            | CommandKind::Sync { .. } => false,
+            CommandKind::Unknown { id, .. } => !matches!(
+                id,
+                // Keep Alive, Save Game, Load Game, Restart Game
+                0x05..=0x08
+                    // Pause, Resume
+                    | 0x10
+                    | 0x11
+                    // Lobby commands (Start Game, Change Race, etc.)
+                    | 0x3c..=0x48
+                    // Briefing Start, Latency, Replay Speed
+                    | 0x54..=0x56
+                    // Make Game Public, Chat
+                    | 0x5b
+                    | 0x5c
+            ),
+            _ => true,
+        }
+    }
+}
+
+/// How to determine the length of the data following a custom command's ID.
+#[derive(Debug, Copy, Clone)]
+pub enum CommandLength {
+    /// The command always has this many bytes of data.
+    Fixed(usize),
+    /// The command's length depends on its data. The function will be passed the remainder of the
+    /// frame's command block (starting just after the command ID) and should return the length of
+    /// the command's data, or [None] if it can't be determined. If [None] is returned, or the
+    /// length extends past the end of the block, the rest of the block will be treated as an
+    /// [CommandKind::Unknown] command.
+    Variable(fn(&[u8]) -> Option<usize>),
+}
+
+/// A function that decodes the data of a custom command (not including the command ID).
+pub type CommandDecodeFn = fn(id: u8, data: &[u8]) -> CommandKind;
+
+#[derive(Debug, Copy, Clone)]
+struct CommandDecoder {
+    length: CommandLength,
+    decode: CommandDecodeFn,
+}
+
+/// A set of decoders for commands that broodrep doesn't know about (e.g. those added by modded
+/// clients or bots). Registered decoders are consulted before the built-in ones, so they can also
+/// be used to override how a known command is decoded.
+#[derive(Debug, Clone, Default)]
+pub struct CommandDecoderRegistry {
+    decoders: HashMap<u8, CommandDecoder>,
+}
+
+impl CommandDecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a decoder for the command with the specified ID, replacing any decoder that was
+    /// previously registered for it.
+    pub fn register(&mut self, id: u8, length: CommandLength, decode: CommandDecodeFn) {
+        self.decoders.insert(id, CommandDecoder { length, decode });
+    }
+
+    /// Removes the decoder for the command with the specified ID, if one was registered.
+    pub fn unregister(&mut self, id: u8) {
+        self.decoders.remove(&id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+}
+
+const CMD_SELECT: u8 = 0x09;
+const CMD_SELECT_ADD: u8 = 0x0a;
+const CMD_SELECT_REMOVE: u8 = 0x0b;
+const CMD_BUILD: u8 = 0x0c;
+const CMD_HOTKEY: u8 = 0x13;
+const CMD_RIGHT_CLICK: u8 = 0x14;
+const CMD_CANCEL_BUILD: u8 = 0x18;
+const CMD_CANCEL_MORPH: u8 = 0x19;
+const CMD_TRAIN: u8 = 0x1f;
+const CMD_CANCEL_TRAIN: u8 = 0x20;
+const CMD_UNIT_MORPH: u8 = 0x23;
+const CMD_RESEARCH: u8 = 0x30;
+const CMD_CANCEL_RESEARCH: u8 = 0x31;
+const CMD_UPGRADE: u8 = 0x32;
+const CMD_CANCEL_UPGRADE: u8 = 0x33;
+const CMD_CANCEL_ADDON: u8 = 0x34;
+const CMD_BUILDING_MORPH: u8 = 0x35;
+const CMD_SYNC: u8 = 0x37;
+const CMD_SET_LATENCY: u8 = 0x55;
+const CMD_LEAVE_GAME: u8 = 0x57;
+const CMD_CHAT: u8 = 0x5c;
+const CMD_RIGHT_CLICK_121: u8 = 0x60;
+const CMD_SELECT_121: u8 = 0x63;
+const CMD_SELECT_ADD_121: u8 = 0x64;
+const CMD_SELECT_REMOVE_121: u8 = 0x65;
+
+/// Returns the length of the data following the ID for a particular command, or [None] if the
+/// command isn't known or its length can't be determined from `data`.
+fn command_len(id: u8, data: &[u8]) -> Option<usize> {
+    let len = match id {
+        0x05 | 0x08 | 0x10 | 0x11 | 0x18 | 0x19 | 0x1b | 0x1c | 0x1d | 0x27 | 0x2a | 0x2e
+        | 0x31 | 0x33 | 0x34 | 0x36 | 0x38 | 0x39 | 0x3c | 0x54 | 0x5a | 0x5b => 0,
+        0x0f | 0x1a | 0x1e | 0x21 | 0x22 | 0x25 | 0x26 | 0x28 | 0x2b | 0x2c | 0x2d | 0x30
+        | 0x32 | 0x3a | 0x3b | 0x3d | 0x42 | 0x43 | 0x55 | 0x57 => 1,
+        0x0d | 0x13 | 0x1f | 0x20 | 0x23 | 0x29 | 0x35 | 0x41 | 0x44 | 0x45 => 2,
+        0x0e | 0x12 | 0x2f | 0x58 | 0x62 => 4,
+        0x3e => 5,
+        0x37 => 6,
+        0x0c | 0x3f => 7,
+        0x14 | 0x56 => 9,
+        0x15 => 10,
+        0x60 => 11,
+        0x48 | 0x61 => 12,
+        0x40 => 17,
+        0x5c => 81,
+        // Select, Select Add, Select Remove
+        0x09..=0x0b => 1 + *data.first()? as usize * 2,
+        // SC:R versions of Select, Select Add, Select Remove
+        0x63..=0x65 => 1 + *data.first()? as usize * 4,
+        // Save Game, Load Game: u32 followed by a null-terminated filename
+        0x06 | 0x07 => 4 + data.get(4..)?.iter().position(|&b| b == 0)? + 1,
+        _ => return None,
+    };
+
+    Some(len)
+}
+
+fn parse_command(id: u8, mut data: &[u8]) -> Result<CommandKind, CommandError> {
+    let kind = match id {
+        CMD_BUILD => CommandKind::Build {
+            order: data.read_u8()?,
+            x: data.read_u16::<LE>()?,
+            y: data.read_u16::<LE>()?,
+            unit_type: data.read_u16::<LE>()?,
+        },
+        CMD_TRAIN => CommandKind::Train {
+            unit_type: data.read_u16::<LE>()?,
+        },
+        CMD_UNIT_MORPH => CommandKind::UnitMorph {
+            unit_type: data.read_u16::<LE>()?,
+        },
+        CMD_BUILDING_MORPH => CommandKind::BuildingMorph {
+            unit_type: data.read_u16::<LE>()?,
+        },
+        CMD_RESEARCH => CommandKind::Research {
+            tech: data.read_u8()?,
+        },
+        CMD_UPGRADE => CommandKind::Upgrade {
+            upgrade: data.read_u8()?,
+        },
+        CMD_CANCEL_BUILD => CommandKind::CancelBuild,
+        CMD_CANCEL_MORPH => CommandKind::CancelMorph,
+        CMD_CANCEL_TRAIN => CommandKind::CancelTrain {
+            unit_tag: data.read_u16::<LE>()?,
+        },
+        CMD_CANCEL_ADDON => CommandKind::CancelAddon,
+        CMD_CANCEL_RESEARCH => CommandKind::CancelResearch,
+        CMD_CANCEL_UPGRADE => CommandKind::CancelUpgrade,
+        CMD_HOTKEY => CommandKind::Hotkey {
+            action: data.read_u8()?.into(),
+            group: data.read_u8()?,
+        },
+        CMD_CHAT => {
+            let sender_slot = data.read_u8()?;
+            let message = data.split(|&b| b == 0).next().unwrap_or_default();
+            CommandKind::Chat {
+                sender_slot,
+                message: String::from_utf8_lossy(message).into_owned(),
+            }
+        }
+        CMD_SET_LATENCY => CommandKind::SetLatency {
+            turn_rate: data.read_u8()?.into(),
+        },
+        CMD_LEAVE_GAME => CommandKind::LeaveGame {
+            reason: data.read_u8()?.into(),
+        },
+        CMD_SYNC => CommandKind::Sync {
+            data: data.to_vec(),
+        },
+        CMD_RIGHT_CLICK | CMD_RIGHT_CLICK_121 => {
+            let x = data.read_u16::<LE>()?;
+            let y = data.read_u16::<LE>()?;
+            let target_unit = data.read_u16::<LE>()?;
+            if id == CMD_RIGHT_CLICK_121 {
+                data.read_u16::<LE>()?; // unknown
+            }
+            let unit_type = data.read_u16::<LE>()?;
+            let queued = data.read_u8()? != 0;
+            CommandKind::RightClick {
+                x,
+                y,
+                target_unit: (target_unit != 0).then_some(target_unit),
+                unit_type,
+                queued,
+            }
+        }
+        CMD_SELECT
+        | CMD_SELECT_ADD
+        | CMD_SELECT_REMOVE
+        | CMD_SELECT_121
+        | CMD_SELECT_ADD_121
+        | CMD_SELECT_REMOVE_121 => {
+            let mode = match id {
+                CMD_SELECT | CMD_SELECT_121 => SelectMode::Replace,
+                CMD_SELECT_ADD | CMD_SELECT_ADD_121 => SelectMode::Add,
+                _ => SelectMode::Remove,
+            };
+            let count = data.read_u8()?;
+            let unit_tags = (0..count)
+                .map(|_| {
+                    let tag = data.read_u16::<LE>()?;
+                    if id >= CMD_SELECT_121 {
+                        data.read_u16::<LE>()?; // unknown
+                    }
+                    Ok(tag)
+                })
+                .collect::<Result<_, CommandError>>()?;
+            CommandKind::Select { mode, unit_tags }
+        }
+        _ => CommandKind::Unknown {
+            id,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok(kind)
+}
+
+/// Parses the commands in a single frame block and appends them to `out`. `block_offset` is the
+/// offset of the block within the command data, and is only used for error reporting.
+fn parse_frame_block(
+    frame: u32,
+    block_offset: usize,
+    mut block: &[u8],
+    registry: &CommandDecoderRegistry,
+    config: &CommandParseConfig,
+    out: &mut Vec<Command>,
+) -> Result<(), CommandError> {
+    let block_len = block.len();
+    while !block.is_empty() {
+        let offset = block_offset + (block_len - block.len());
+        let player_id = block.read_u8()?;
+        let id = match block.read_u8() {
+            Ok(id) => id,
+            // A lone player ID at the end of a block, nothing we can do with it
+            Err(_) if config.strict => return Err(CommandError::TruncatedFrame { offset }),
+            Err(_) => break,
+        };
+
+        let custom = registry.decoders.get(&id);
+        let len = match custom {
+            Some(CommandDecoder {
+                length: CommandLength::Fixed(len),
+                ..
+            }) => Some(*len),
+            Some(CommandDecoder {
+                length: CommandLength::Variable(len_fn),
+                ..
+            }) => len_fn(block),
+            None => command_len(id, block),
+        };
+        let (len, known) = match len {
+            Some(len) if len <= block.len() => (len, true),
+            Some(_) if config.strict => {
+                return Err(CommandError::LengthMismatch { frame, offset, id });
+            }
+            None if config.strict => {
+                return Err(CommandError::UnknownLength { frame, offset, id });
+            }
+            // We don't know how long this command is (or it claims to be longer than the block),
+            // so the rest of the block can't be reliably decoded. Preserve it as-is.
+            _ => (block.len(), false),
+        };
+        let (data, rest) = block.split_at(len);
+        block = rest;
+
+        let kind = if let (true, Some(custom)) = (known, custom) {
+            (custom.decode)(id, data)
+        } else if known {
+            parse_command(id, data)?
+        } else {
+            CommandKind::Unknown {
+                id,
+                data: data.to_vec(),
+            }
+        };
+        out.push(Command {
+            frame,
+            player_id,
+            kind,
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses the (decompressed) data of a replay's Commands section, using `registry` to decode any
+/// custom commands.
+pub fn parse_commands(
+    data: &[u8],
+    registry: &CommandDecoderRegistry,
+    config: &CommandParseConfig,
+) -> Result<Vec<Command>, CommandError> {
+    let mut commands = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let (frame, block) = read_frame_block(data, offset)?;
+        // Skip past the frame number and size
+        let block_offset = offset + 5;
+        parse_frame_block(frame, block_offset, block, registry, config, &mut commands)?;
+        offset = block_offset + block.len();
+    }
+
+    Ok(commands)
+}
+
+/// Reads the header of the frame block starting at `offset` in `data`, returning the frame number
+/// and the block's command data.
+fn read_frame_block(data: &[u8], offset: usize) -> Result<(u32, &[u8]), CommandError> {
+    let mut cursor = &data[offset..];
+    let frame = cursor
+        .read_u32::<LE>()
+        .map_err(|_| CommandError::TruncatedFrame { offset })?;
+    let size = cursor
+        .read_u8()
+        .map_err(|_| CommandError::TruncatedFrame { offset })? as usize;
+    if size > cursor.len() {
+        return Err(CommandError::TruncatedFrame { offset });
+    }
+
+    Ok((frame, &cursor[..size]))
+}
+
+/// The commands executed on a single frame, as returned by [CommandFrames].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameCommands {
+    pub frame: u32,
+    pub commands: Vec<Command>,
+}
+
+/// An iterator over the frames in a replay's command stream, created by
+/// [Replay::command_frames](crate::Replay::command_frames). Commands are decoded one frame at a
+/// time as the iterator is advanced.
+///
+/// If a frame can't be parsed, the error will be returned and iteration will end, as the
+/// location of the following frames can't be determined.
+pub struct CommandFrames<'a> {
+    data: Vec<u8>,
+    offset: usize,
+    registry: &'a CommandDecoderRegistry,
+    config: CommandParseConfig,
+}
+
+impl<'a> CommandFrames<'a> {
+    pub(crate) fn new(
+        data: Vec<u8>,
+        registry: &'a CommandDecoderRegistry,
+        config: CommandParseConfig,
+    ) -> Self {
+        Self {
+            data,
+            offset: 0,
+            registry,
+            config,
+        }
+    }
+
+    fn parse_next(&mut self) -> Result<FrameCommands, CommandError> {
+        let (frame, block) = read_frame_block(&self.data, self.offset)?;
+        let block_offset = self.offset + 5;
+        let mut commands = Vec::new();
+        parse_frame_block(
+            frame,
+            block_offset,
+            block,
+            self.registry,
+            &self.config,
+            &mut commands,
+        )?;
+        self.offset = block_offset + block.len();
+        Ok(FrameCommands { frame, commands })
+    }
+}
+
+impl Iterator for CommandFrames<'_> {
+    type Item = Result<FrameCommands, CommandError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+
+        let result = self.parse_next();
+        if result.is_err() {
+            self.offset = self.data.len();
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_block(frame: u32, commands: &[&[u8]]) -> Vec<u8> {
+        let block = commands.concat();
+        let mut data = Vec::new();
+        data.extend_from_slice(&frame.to_le_bytes());
+        data.push(block.len() as u8);
+        data.extend_from_slice(&block);
+        data
+    }
+
+    #[test]
+    fn right_click_ground() {
+        let data = frame_block(
+            42,
+            &[&[
+                0, 0x14, 0x10, 0x02, 0xa0, 0x0f, 0x00, 0x00, 0xe4, 0x00, 0x00,
+            ]],
+        );
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands,
+            [Command {
+                frame: 42,
+                player_id: 0,
+                kind: CommandKind::RightClick {
+                    x: 528,
+                    y: 4000,
+                    target_unit: None,
+                    unit_type: 228,
+                    queued: false,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn right_click_unit_queued() {
+        let data = frame_block(
+            100,
+            &[
+                &[
+                    1, 0x14, 0x40, 0x00, 0x80, 0x00, 0x34, 0x12, 0x25, 0x00, 0x01,
+                ],
+                &[
+                    1, 0x60, 0x41, 0x00, 0x81, 0x00, 0x35, 0x12, 0x00, 0x00, 0x26, 0x00, 0x00,
+                ],
+            ],
+        );
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands,
+            [
+                Command {
+                    frame: 100,
+                    player_id: 1,
+                    kind: CommandKind::RightClick {
+                        x: 64,
+                        y: 128,
+                        target_unit: Some(0x1234),
+                        unit_type: 37,
+                        queued: true,
+                    },
+                },
+                Command {
+                    frame: 100,
+                    player_id: 1,
+                    kind: CommandKind::RightClick {
+                        x: 65,
+                        y: 129,
+                        target_unit: Some(0x1235),
+                        unit_type: 38,
+                        queued: false,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn production() {
+        let data = frame_block(
+            500,
+            &[
+                &[0, 0x0c, 0x1e, 0x20, 0x00, 0x31, 0x00, 0x8e, 0x00],
+                &[0, 0x1f, 0x07, 0x00],
+                &[1, 0x23, 0x25, 0x00],
+                &[1, 0x35, 0x84, 0x00],
+                &[1, 0x30, 0x0b],
+                &[1, 0x32, 0x1b],
+                &[1, 0x20, 0xfe, 0x00],
+                &[1, 0x33],
+                &[1, 0x13, 0x00, 0x04],
+                &[1, 0x13, 0x01, 0x04],
+                &[1, 0x57, 0x01],
+            ],
+        );
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands.iter().map(|c| &c.kind).collect::<Vec<_>>(),
+            [
+                &CommandKind::Build {
+                    order: 0x1e,
+                    x: 32,
+                    y: 49,
+                    unit_type: 142,
+                },
+                &CommandKind::Train { unit_type: 7 },
+                &CommandKind::UnitMorph { unit_type: 37 },
+                &CommandKind::BuildingMorph { unit_type: 132 },
+                &CommandKind::Research { tech: 11 },
+                &CommandKind::Upgrade { upgrade: 27 },
+                &CommandKind::CancelTrain { unit_tag: 254 },
+                &CommandKind::CancelUpgrade,
+                &CommandKind::Hotkey {
+                    action: HotkeyAction::Assign,
+                    group: 4,
+                },
+                &CommandKind::Hotkey {
+                    action: HotkeyAction::Select,
+                    group: 4,
+                },
+                &CommandKind::LeaveGame {
+                    reason: LeaveReason::Quit,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_command_preserves_rest_of_block() {
+        let data = [
+            frame_block(1, &[&[0, 0x05], &[0, 0xf0, 0xaa, 0xbb]]),
+            frame_block(2, &[&[0, 0x05]]),
+        ]
+        .concat();
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(commands.len(), 3);
+        assert_eq!(
+            commands[1].kind,
+            CommandKind::Unknown {
+                id: 0xf0,
+                data: vec![0xaa, 0xbb],
+            }
+        );
+        assert_eq!(commands[2].frame, 2);
+    }
+
+    #[test]
+    fn select() {
+        let data = frame_block(
+            1,
+            &[
+                &[0, 0x09, 2, 0x01, 0x00, 0x02, 0x00],
+                &[0, 0x64, 1, 0x03, 0x00, 0xff, 0xff],
+            ],
+        );
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands.iter().map(|c| &c.kind).collect::<Vec<_>>(),
+            [
+                &CommandKind::Select {
+                    mode: SelectMode::Replace,
+                    unit_tags: vec![1, 2],
+                },
+                &CommandKind::Select {
+                    mode: SelectMode::Add,
+                    unit_tags: vec![3],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn chat() {
+        let mut chat = vec![0u8, 0x5c, 2];
+        let mut message = [0u8; 80];
+        message[..8].copy_from_slice(b"gg, wp\n!");
+        message[10] = b'x'; // garbage after the null terminator
+        chat.extend_from_slice(&message);
+        let data = frame_block(1, &[&chat]);
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands[0].kind,
+            CommandKind::Chat {
+                sender_slot: 2,
+                message: "gg, wp\n!".into(),
+            }
+        );
+        assert!(!commands[0].kind.is_action());
+    }
+
+    #[test]
+    fn sync() {
+        let data = frame_block(1, &[&[1, 0x37, 1, 2, 3, 4, 5, 6], &[1, 0x1f, 7, 0]]);
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands[0].kind,
+            CommandKind::Sync {
+                data: vec![1, 2, 3, 4, 5, 6],
+            }
+        );
+        assert_eq!(commands[0].kind.command_type(), CommandType::Sync);
+        assert!(!commands[0].kind.is_action());
+        assert_eq!(commands[1].kind, CommandKind::Train { unit_type: 7 });
+    }
+
+    #[test]
+    fn corrupted_block() {
+        // The Train command should have 2 bytes of data, but the block ends after 1
+        let data = [
+            frame_block(1, &[&[0, 0x05]]),
+            frame_block(2, &[&[0, 0x05], &[1, 0x1f, 0x07]]),
+            frame_block(3, &[&[0, 0x05]]),
+        ]
+        .concat();
+
+        let commands = parse_commands(
+            &data,
+            &CommandDecoderRegistry::new(),
+            &CommandParseConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(commands.len(), 4);
+        assert_eq!(
+            commands[2].kind,
+            CommandKind::Unknown {
+                id: 0x1f,
+                data: vec![0x07],
+            }
+        );
+
+        let strict = CommandParseConfig { strict: true };
+        assert!(matches!(
+            parse_commands(&data, &CommandDecoderRegistry::new(), &strict),
+            Err(CommandError::LengthMismatch {
+                frame: 2,
+                offset: 14,
+                id: 0x1f
+            })
+        ));
+
+        let data = frame_block(1, &[&[0, 0x05], &[0, 0xf0, 0xaa]]);
+        assert!(matches!(
+            parse_commands(&data, &CommandDecoderRegistry::new(), &strict),
+            Err(CommandError::UnknownLength {
+                frame: 1,
+                offset: 7,
+                id: 0xf0
+            })
+        ));
+    }
+
+    #[test]
+    fn custom_decoders() {
+        let mut registry = CommandDecoderRegistry::new();
+        registry.register(0xf0, CommandLength::Fixed(2), |id, data| {
+            CommandKind::Custom {
+                id,
+                data: data.to_vec(),
+            }
+        });
+        // Length-prefixed
+        registry.register(
+            0xf1,
+            CommandLength::Variable(|data| Some(1 + *data.first()? as usize)),
+            |id, data| CommandKind::Custom {
+                id,
+                data: data[1..].to_vec(),
+            },
+        );
+
+        let data = frame_block(
+            7,
+            &[
+                &[0, 0xf0, 0xaa, 0xbb],
+                &[1, 0xf1, 0x03, 0x01, 0x02, 0x03],
+                &[1, 0x05],
+                // Claims to be longer than the block
+                &[0, 0xf1, 0x08, 0x01],
+            ],
+        );
+        let commands = parse_commands(&data, &registry, &CommandParseConfig::default()).unwrap();
+        assert_eq!(
+            commands.iter().map(|c| &c.kind).collect::<Vec<_>>(),
+            [
+                &CommandKind::Custom {
+                    id: 0xf0,
+                    data: vec![0xaa, 0xbb],
+                },
+                &CommandKind::Custom {
+                    id: 0xf1,
+                    data: vec![0x01, 0x02, 0x03],
+                },
+                &CommandKind::Unknown {
+                    id: 0x05,
+                    data: vec![],
+                },
+                &CommandKind::Unknown {
+                    id: 0xf1,
+                    data: vec![0x08, 0x01],
+                },
+            ]
+        );
+
+        registry.unregister(0xf0);
+        let commands = parse_commands(&data, &registry, &CommandParseConfig::default()).unwrap();
+        assert_eq!(
+            commands[0].kind,
+            CommandKind::Unknown {
+                id: 0xf0,
+                data: data[7..].to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let mut data = frame_block(1, &[&[0, 0x05]]);
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            parse_commands(
+                &data,
+                &CommandDecoderRegistry::new(),
+                &CommandParseConfig::default()
+            ),
+            Err(CommandError::TruncatedFrame { offset: 0 })
+        ));
+    }
+
+    #[test]
+    fn command_frames() {
+        let mut data = frame_block(10, &[&[0, 0x1f, 0x07, 0x00], &[1, 0x33]]);
+        data.extend(frame_block(20, &[]));
+        data.extend(frame_block(30, &[&[0, 0x31]]));
+        let registry = CommandDecoderRegistry::new();
+        let frames = CommandFrames::new(data.clone(), &registry, Default::default())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            frames.iter().map(|f| f.frame).collect::<Vec<_>>(),
+            [10, 20, 30]
+        );
+        assert_eq!(
+            frames
+                .into_iter()
+                .flat_map(|f| f.commands)
+                .collect::<Vec<_>>(),
+            parse_commands(&data, &registry, &Default::default()).unwrap()
+        );
+
+        data.extend_from_slice(&[40, 0, 0, 0, 5, 0]);
+        let mut frames = CommandFrames::new(data, &registry, Default::default());
+        assert_eq!(frames.by_ref().take(3).filter(|f| f.is_ok()).count(), 3);
+        assert!(matches!(
+            frames.next(),
+            Some(Err(CommandError::TruncatedFrame { .. }))
+        ));
+        assert!(frames.next().is_none());
+    }
+}
@@ -9,6 +9,11 @@ use thiserror::Error;
 pub struct DecompressionConfig {
     /// Maximum bytes to decompress (default: 100MB)
     pub max_decompressed_size: u64,
+    /// Maximum bytes to decompress over the lifetime of a [Replay](crate::Replay), across all of
+    /// the sections read from it (default: no limit). Note that reading the same section multiple
+    /// times (e.g. calling [Replay::commands](crate::Replay::commands) repeatedly) counts against
+    /// this limit each time.
+    pub max_total_decompressed_size: Option<u64>,
     /// Maximum compression ratio allowed (default: 500:1)
     pub max_compression_ratio: f64,
     /// Maximum time to spend decompressing (default: 30 seconds)
@@ -19,6 +24,7 @@ impl Default for DecompressionConfig {
     fn default() -> Self {
         Self {
             max_decompressed_size: 100 * 1024 * 1024, // 100MB
+            max_total_decompressed_size: None,
             max_compression_ratio: 500.0,
             max_decompression_time: Some(Duration::from_secs(30)),
         }
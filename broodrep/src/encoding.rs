@@ -0,0 +1,225 @@
+//! Decoding of strings stored in the replay header (player names, game title, etc.).
+//!
+//! StarCraft writes these strings in whatever encoding the recording player's system was using,
+//! and doesn't record which one that was. Modern versions use UTF-8, but older replays (and players
+//! on older systems) will often have names in a legacy code page instead.
+
+/// A legacy text encoding that strings in a replay may be decoded as if they aren't valid UTF-8.
+///
+/// More encodings may be added in the future (and some are only available with a feature enabled),
+/// so this can't be matched exhaustively.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TextEncoding {
+    /// Windows-1251, used by Cyrillic (Russian, Ukrainian, Bulgarian, etc.) systems.
+    Windows1251,
+    /// Windows-1252, used by most Western European systems. This is a superset of ISO-8859-1.
+    Windows1252,
+    /// Code page 949 (Unified Hangul Code), used by Korean systems. This is a superset of EUC-KR.
+    ///
+    /// Requires the `cp949` feature, which adds a dependency on `encoding_rs`.
+    #[cfg(feature = "cp949")]
+    Cp949,
+}
+
+/// Characters for the bytes 0x80-0xBF in Windows-1251 (0xC0-0xFF map directly to U+0410-U+044F).
+const WINDOWS_1251_HIGH: [char; 64] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ', //
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{fffd}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ', //
+    '\u{a0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{ad}', '®',
+    'Ї', //
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї', //
+];
+
+/// Characters for the bytes 0x80-0x9F in Windows-1252 (0xA0-0xFF match ISO-8859-1).
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{fffd}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{fffd}', 'Ž',
+    '\u{fffd}', '\u{fffd}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{fffd}',
+    'ž', 'Ÿ',
+];
+
+impl TextEncoding {
+    /// Decodes `bytes` in this encoding. Bytes that aren't defined in the encoding are replaced
+    /// with U+FFFD REPLACEMENT CHARACTER.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Windows1251 => decode_single_byte(bytes, |b| match b {
+                0x80..=0xBF => WINDOWS_1251_HIGH[(b - 0x80) as usize],
+                _ => char::from_u32(0x410 + (b - 0xC0) as u32).unwrap(),
+            }),
+            TextEncoding::Windows1252 => decode_single_byte(bytes, |b| match b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                _ => b as char,
+            }),
+            #[cfg(feature = "cp949")]
+            TextEncoding::Cp949 => {
+                // encoding_rs implements EUC-KR as the WHATWG Encoding Standard defines it, which
+                // is Microsoft's extension of it (i.e. CP949)
+                let (decoded, _) = encoding_rs::EUC_KR.decode_without_bom_handling(bytes);
+                decoded.into_owned()
+            }
+        }
+    }
+}
+
+/// Decodes an ASCII-compatible single byte encoding, using `high` to map the bytes 0x80-0xFF.
+fn decode_single_byte(bytes: &[u8], high: impl Fn(u8) -> char) -> String {
+    bytes
+        .iter()
+        .map(|&b| if b < 0x80 { b as char } else { high(b) })
+        .collect()
+}
+
+/// How strings in the replay header (player names, game title, etc.) are decoded. See
+/// [ParseConfig::string_encoding](crate::ParseConfig::string_encoding).
+///
+/// Like [TextEncoding], some variants are only available with a feature enabled, so this can't be
+/// matched exhaustively.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StringEncoding {
+    /// Decode strings as UTF-8, replacing invalid sequences with U+FFFD REPLACEMENT CHARACTER.
+    Utf8Lossy,
+    /// Decode every string as CP949, even ones that are also valid UTF-8. Useful when all of the
+    /// replays being parsed are known to come from Korean systems.
+    ///
+    /// Requires the `cp949` feature.
+    #[cfg(feature = "cp949")]
+    Cp949,
+    /// Use strings that are valid UTF-8 as-is. Other strings are decoded as each of the
+    /// [fallback encodings](crate::ParseConfig::fallback_encodings) and then as CP949 (if the
+    /// `cp949` feature is enabled), and whichever result contains the fewest replacement
+    /// characters is used.
+    #[default]
+    Auto,
+}
+
+fn replacement_count(s: &str) -> usize {
+    s.chars()
+        .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+        .count()
+}
+
+/// Decodes a string from the replay using `encoding`. For [StringEncoding::Auto], valid UTF-8 is
+/// always used as-is. Otherwise, the string is decoded as (lossy) UTF-8, as each of the
+/// `fallbacks`, and as CP949 (if enabled), and whichever result contains the fewest replacement
+/// characters is picked, preferring them in that order on ties.
+pub(crate) fn decode_string(
+    bytes: &[u8],
+    encoding: StringEncoding,
+    fallbacks: &[TextEncoding],
+) -> String {
+    match encoding {
+        StringEncoding::Utf8Lossy => String::from_utf8_lossy(bytes).into_owned(),
+        #[cfg(feature = "cp949")]
+        StringEncoding::Cp949 => TextEncoding::Cp949.decode(bytes),
+        StringEncoding::Auto => decode_auto(bytes, fallbacks),
+    }
+}
+
+fn decode_auto(bytes: &[u8], fallbacks: &[TextEncoding]) -> String {
+    if let Ok(s) = str::from_utf8(bytes) {
+        return s.to_owned();
+    }
+
+    let mut best = String::from_utf8_lossy(bytes).into_owned();
+    let mut best_count = replacement_count(&best);
+    #[cfg(feature = "cp949")]
+    let fallbacks = fallbacks
+        .iter()
+        .chain(std::iter::once(&TextEncoding::Cp949));
+    for encoding in fallbacks {
+        if best_count == 0 {
+            break;
+        }
+        let decoded = encoding.decode(bytes);
+        let count = replacement_count(&decoded);
+        if count < best_count {
+            best = decoded;
+            best_count = count;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_auto(bytes: &[u8], fallbacks: &[TextEncoding]) -> String {
+        decode_string(bytes, StringEncoding::Auto, fallbacks)
+    }
+
+    #[test]
+    fn fallback_decoding() {
+        // "Привет" in Windows-1251
+        let cyrillic = [0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2];
+        #[cfg(not(feature = "cp949"))]
+        assert_eq!(decode_auto(&cyrillic, &[]), "\u{fffd}".repeat(6));
+        assert_eq!(
+            decode_auto(&cyrillic, &[TextEncoding::Windows1251]),
+            "Привет"
+        );
+        // Both decode every byte, so the first one listed wins
+        assert_eq!(
+            decode_auto(
+                &cyrillic,
+                &[TextEncoding::Windows1252, TextEncoding::Windows1251]
+            ),
+            "Ïðèâåò"
+        );
+        // 0x81 isn't defined in Windows-1252, so Windows-1251 is preferred despite being listed
+        // second
+        assert_eq!(
+            decode_auto(
+                &[0x81, 0xE0],
+                &[TextEncoding::Windows1252, TextEncoding::Windows1251]
+            ),
+            "Ѓа"
+        );
+        assert_eq!(
+            decode_auto("Jürgen".as_bytes(), &[TextEncoding::Windows1251]),
+            "Jürgen"
+        );
+        // Fallbacks are only used in Auto mode
+        assert_eq!(
+            decode_string(
+                &cyrillic,
+                StringEncoding::Utf8Lossy,
+                &[TextEncoding::Windows1251]
+            ),
+            "\u{fffd}".repeat(6)
+        );
+    }
+
+    #[cfg(feature = "cp949")]
+    #[test]
+    fn cp949_decoding() {
+        // "테란" in CP949
+        let korean = [0xC5, 0xD7, 0xB6, 0xF5];
+        assert_eq!(decode_auto(&korean, &[]), "테란");
+        // A lead byte without a valid trail byte doesn't swallow the character after it
+        assert_eq!(
+            TextEncoding::Cp949.decode(&[b'a', 0xC5, b'!']),
+            "a\u{fffd}!"
+        );
+        // 0x8141 is an extended (non-EUC-KR) syllable
+        assert_eq!(TextEncoding::Cp949.decode(&[0x81, 0x41]), "갂");
+        assert_eq!(
+            TextEncoding::Cp949.decode(&[0xB0, 0xA1, 0xC7, 0xD1, 0xB1, 0xDB, 0xC8, 0xFE]),
+            "가한글힝"
+        );
+        // Explicit fallbacks are preferred over CP949 when both decode every byte
+        assert_eq!(decode_auto(&korean, &[TextEncoding::Windows1252]), "Å×¶õ");
+        assert_eq!(
+            decode_auto(&[0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2], &[]),
+            "厥外奄"
+        );
+        // "é" is valid UTF-8, but the explicit mode always uses CP949
+        assert_eq!(decode_auto("é".as_bytes(), &[]), "é");
+        assert_eq!(
+            decode_string("é".as_bytes(), StringEncoding::Cp949, &[]),
+            "챕"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::{borrow::Cow, io::Write, time::Duration};
+
+use crate::commands::{Command, CommandKind};
+#[cfg(feature = "serde")]
+use crate::{BroodrepError, Player, ReplayFormat, ReplayHeader};
+
+/// The header row written by [write_commands_csv].
+const CSV_HEADER: &str = "frame,real_time_s,player_id,command_type,unit_type,x,y,value,text";
+
+/// Quotes a CSV field if it contains characters that would otherwise break the row.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn write_commands_csv<W: Write>(
+    mut w: W,
+    commands: &[Command],
+    time_per_step: Duration,
+) -> std::io::Result<()> {
+    writeln!(w, "{CSV_HEADER}")?;
+    for command in commands {
+        let mut unit_type = None;
+        let mut position = None;
+        let mut value = None;
+        let mut text = Cow::Borrowed("");
+        match command.kind {
+            CommandKind::Select { ref unit_tags, .. } => {
+                value = Some(unit_tags.len() as u32);
+                text = Cow::Owned(
+                    unit_tags
+                        .iter()
+                        .map(|t| t.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                );
+            }
+            CommandKind::RightClick {
+                x,
+                y,
+                target_unit,
+                unit_type: t,
+                ..
+            } => {
+                unit_type = Some(t);
+                position = Some((x, y));
+                value = target_unit.map(u32::from);
+            }
+            CommandKind::Build {
+                x, y, unit_type: t, ..
+            } => {
+                unit_type = Some(t);
+                position = Some((x, y));
+            }
+            CommandKind::Train { unit_type: t }
+            | CommandKind::UnitMorph { unit_type: t }
+            | CommandKind::BuildingMorph { unit_type: t } => unit_type = Some(t),
+            CommandKind::Research { tech } => value = Some(tech.into()),
+            CommandKind::Upgrade { upgrade } => value = Some(upgrade.into()),
+            CommandKind::CancelTrain { unit_tag } => value = Some(unit_tag.into()),
+            CommandKind::Chat {
+                sender_slot,
+                ref message,
+            } => {
+                value = Some(sender_slot.into());
+                text = Cow::Borrowed(message);
+            }
+            CommandKind::Hotkey { action, group } => {
+                value = Some(group.into());
+                text = Cow::Owned(format!("{action:?}"));
+            }
+            CommandKind::SetLatency { turn_rate } => text = Cow::Owned(turn_rate.to_string()),
+            CommandKind::LeaveGame { reason } => value = Some(u8::from(reason).into()),
+            CommandKind::Sync { ref data }
+            | CommandKind::Custom { ref data, .. }
+            | CommandKind::Unknown { ref data, .. } => {
+                text = Cow::Owned(hex(data));
+            }
+            CommandKind::CancelBuild
+            | CommandKind::CancelMorph
+            | CommandKind::CancelAddon
+            | CommandKind::CancelResearch
+            | CommandKind::CancelUpgrade => {}
+        }
+
+        let opt = |v: Option<u32>| v.map(|v| v.to_string()).unwrap_or_default();
+        writeln!(
+            w,
+            "{},{:.3},{},{},{},{},{},{},{}",
+            command.frame,
+            (time_per_step * command.frame).as_secs_f64(),
+            command.player_id,
+            command.kind.command_type(),
+            opt(unit_type.map(u32::from)),
+            opt(position.map(|(x, _)| x.into())),
+            opt(position.map(|(_, y)| y.into())),
+            opt(value),
+            csv_field(&text),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The object written by [Replay::to_json](crate::Replay::to_json).
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct ReplayJson<'a> {
+    format: ReplayFormat,
+    duration_ms: u64,
+    header: &'a ReplayHeader,
+    players: Vec<&'a Player>,
+    observers: Vec<&'a Player>,
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn header_json(
+    format: ReplayFormat,
+    header: &ReplayHeader,
+) -> Result<String, BroodrepError> {
+    let json = ReplayJson {
+        format,
+        duration_ms: (header.speed.time_per_step() * header.frames).as_millis() as u64,
+        header,
+        players: header.players().collect(),
+        observers: header.observers().collect(),
+    };
+    serde_json::to_string(&json).map_err(|e| BroodrepError::Json(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_csv() {
+        let commands = [
+            Command {
+                frame: 24,
+                player_id: 0,
+                kind: CommandKind::Build {
+                    order: 0x1e,
+                    x: 10,
+                    y: 20,
+                    unit_type: 142,
+                },
+            },
+            Command {
+                frame: 48,
+                player_id: 1,
+                kind: CommandKind::Chat {
+                    sender_slot: 1,
+                    message: "gg, \"wp\"".into(),
+                },
+            },
+            Command {
+                frame: 50,
+                player_id: 1,
+                kind: CommandKind::Unknown {
+                    id: 0x59,
+                    data: vec![0xab, 0x01],
+                },
+            },
+        ];
+        let mut out = Vec::new();
+        write_commands_csv(&mut out, &commands, Duration::from_millis(42)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "frame,real_time_s,player_id,command_type,unit_type,x,y,value,text\n\
+             24,1.008,0,Build,142,10,20,,\n\
+             48,2.016,1,Chat,,,,1,\"gg, \"\"wp\"\"\"\n\
+             50,2.100,1,Unknown(0x59),,,,,ab01\n"
+        );
+    }
+}
@@ -1,35 +1,126 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     ffi::CStr,
     fmt,
-    io::{Cursor, Read, Seek, SeekFrom},
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
 };
 
 use byteorder::{LittleEndian as LE, ReadBytesExt as _};
 use chrono::{DateTime, NaiveDateTime};
 use explode::ExplodeReader;
 use flate2::bufread::ZlibDecoder;
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256};
 use thiserror::Error;
 
+pub use crate::analysis::{
+    ActionCadence, BuildStep, ChatMessage, ControlGroupStats, EapmConfig, IntensityConfig,
+    LeaveEvent, PlayerSpending, ProductionSample, SpendingConfig, SpendingSample, SplicingAnomaly,
+    SuspiciousSpan, SyncFrame,
+};
+pub use crate::bugfixes::BugFixFlags;
+pub use crate::commands::{
+    Command, CommandDecodeFn, CommandDecoderRegistry, CommandError, CommandFrames, CommandKind,
+    CommandLength, CommandParseConfig, CommandType, FrameCommands, HotkeyAction, LeaveReason,
+    SelectMode,
+};
 use crate::compression::SafeDecompressor;
 pub use crate::compression::{DecompressionConfig, DecompressionError};
+pub use crate::encoding::{StringEncoding, TextEncoding};
+pub use crate::map::{Force, MapData, StartLocation, Tileset};
+pub use crate::resolved::ResolvedReplay;
 pub use crate::shieldbattery::{ShieldBatteryData, ShieldBatteryDataError};
+pub use crate::units::{
+    Cost, UNIT_NONE, tech_cost, unit_cost, unit_name, unit_supply, upgrade_cost,
+};
 
+mod analysis;
+pub mod batch;
+mod bugfixes;
+mod commands;
 mod compression;
+mod encoding;
+mod export;
+mod map;
+mod resolved;
 mod shieldbattery;
+mod units;
+pub mod version;
 
 #[derive(Error, Debug)]
 pub enum BroodrepError {
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    /// The data doesn't look like a replay file at all (as opposed to
+    /// [MalformedHeader](BroodrepError::MalformedHeader), which means it looks like a replay but
+    /// is damaged).
+    #[error("not a replay file")]
+    NotAReplay,
     #[error("malformed header: {0}")]
     MalformedHeader(&'static str),
+    #[error("malformed section: {0}")]
+    MalformedSection(&'static str),
     #[error("problem decompressing data: {0}")]
     Decompression(#[from] DecompressionError),
     #[error("duplicate section found: {0:?}")]
     DuplicateSection(ReplaySection),
+    #[error("required section is missing: {0:?}")]
+    MissingSection(ReplaySection),
     #[error("shieldbattery data error: {0}")]
     ShieldBatteryData(#[from] shieldbattery::ShieldBatteryDataError),
+    #[error("command error: {0}")]
+    Command(#[from] commands::CommandError),
+    /// Data couldn't be written as JSON (see `Replay::to_json`, which requires the `serde`
+    /// feature).
+    #[error("JSON serialization error: {0}")]
+    Json(String),
+}
+
+/// Settings that control how a replay is parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseConfig {
+    /// Limits that will be applied when decompressing replay data.
+    pub decompression: DecompressionConfig,
+    /// Whether unrecognized values in the replay header should be treated as errors. By default,
+    /// values broodrep doesn't recognize (e.g. a game speed or player type outside of the valid
+    /// range) are parsed into the `Unknown` variant of their type, so that one odd byte doesn't
+    /// make an otherwise fine replay unreadable. When `strict` is set, these will instead cause
+    /// parsing to fail with [BroodrepError::MalformedHeader].
+    pub strict: bool,
+    /// Settings used when parsing the command stream (e.g. for [Replay::commands]).
+    pub commands: CommandParseConfig,
+    /// How strings in the replay header (player names, game title, etc.) are decoded. By default
+    /// this is [StringEncoding::Auto], which uses strings that are valid UTF-8 as-is and tries
+    /// [ParseConfig::fallback_encodings] (and CP949, with the `cp949` feature) for the rest.
+    pub string_encoding: StringEncoding,
+    /// Encodings to try for strings in the replay header that aren't valid UTF-8, when
+    /// [ParseConfig::string_encoding] is [StringEncoding::Auto]. Each candidate is tried and the one that produces the fewest
+    /// replacement characters is used, with earlier entries winning ties (and UTF-8 winning over
+    /// all of them). Note that single-byte encodings can decode nearly any input, so the order
+    /// of this list matters: put the encoding you expect to be most common first.
+    ///
+    /// This is empty by default, meaning invalid UTF-8 is decoded lossily (or as CP949, if the
+    /// `cp949` feature is enabled and that produces fewer replacement characters).
+    pub fallback_encodings: Vec<TextEncoding>,
+    /// How sections that appear more than once in a modern replay should be handled. SC:R never
+    /// writes duplicate sections, but other clients might.
+    pub duplicate_sections: DuplicateSectionPolicy,
+}
+
+/// How to handle a replay that contains the same section more than once. See
+/// [ParseConfig::duplicate_sections].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DuplicateSectionPolicy {
+    /// Fail to parse the replay with [BroodrepError::DuplicateSection].
+    #[default]
+    Error,
+    /// Use the first copy of the section and ignore any later ones.
+    KeepFirst,
+    /// Use the last copy of the section, replacing any earlier ones.
+    KeepLast,
 }
 
 /// A StarCraft replay, parsed from a [Read] implementation. Only the header will be parsed eagerly,
@@ -37,10 +128,29 @@ pub enum BroodrepError {
 pub struct Replay<R: Read + Seek> {
     inner: R,
     decompression_config: DecompressionConfig,
+    command_decoders: CommandDecoderRegistry,
+    command_parse_config: CommandParseConfig,
+    /// How strings are decoded, used for strings in sections that are parsed on demand (e.g. the
+    /// map's string table).
+    string_encoding: StringEncoding,
+    fallback_encodings: Vec<TextEncoding>,
+    /// The total length of the replay data, in bytes.
+    stream_len: u64,
     /// Offsets from the beginning of the file to the header for a particular section. For modern
     /// sections, this will be the offset of the raw data size. For legacy sections, it's the offset
     /// of the section header.
     section_offsets: HashMap<ReplaySection, u64>,
+    /// A section that was located but extends past the end of the file (or is otherwise cut
+    /// short), if the file was truncated.
+    incomplete_section: Option<ReplaySection>,
+    /// The location of data at the end of a legacy replay that couldn't be read as a section.
+    trailing_data: Option<Range<u64>>,
+    /// The total number of bytes decompressed from this replay so far, used to enforce
+    /// [DecompressionConfig::max_total_decompressed_size].
+    decompressed_total: u64,
+    /// A buffer for holding compressed chunks while they're being decompressed, reused between
+    /// reads to avoid an allocation for every chunk.
+    scratch: Vec<u8>,
     pub format: ReplayFormat,
     pub header: ReplayHeader,
 }
@@ -53,85 +163,222 @@ const SIZE_BFIX: usize = 0x08;
 const SIZE_CUSTOM_COLORS: usize = 0xc0;
 const SIZE_GCFG: usize = 0x19;
 
+/// The largest the buffer used for reading compressed chunks is allowed to stay between reads.
+/// Chunks are typically at most 8KB, so this should only be exceeded by unusual replays.
+const MAX_SCRATCH_CAPACITY: usize = 64 * 1024;
+
+/// The number of bytes from the start of a replay file that [detect_replay_format] needs to see.
+pub const FORMAT_DETECTION_LEN: usize = 30;
+
+/// Offset of the first player slot in the header section.
+const HEADER_SLOTS_OFFSET: usize = 0xa1;
+/// Size of each player slot in the header section.
+const SIZE_HEADER_SLOT: usize = 0x24;
+/// Number of player slots in the header section.
+const NUM_HEADER_SLOTS: usize = 12;
+/// Smallest header section we can parse (i.e. the end of the last player slot).
+const MIN_SIZE_HEADER: usize = HEADER_SLOTS_OFFSET + NUM_HEADER_SLOTS * SIZE_HEADER_SLOT;
+
+impl Replay<BufReader<File>> {
+    /// Opens the replay file at `path` and parses it with default settings. The file is buffered,
+    /// which avoids a lot of small reads while the replay's sections are being located.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BroodrepError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl Replay<Cursor<Vec<u8>>> {
+    /// Parses a replay that is already in memory with default settings, taking ownership of its
+    /// bytes.
+    ///
+    /// This is the recommended way to use broodrep from async code: read the file with your
+    /// runtime's async IO (e.g. `tokio::fs::read`), then parse it with this. Since the data is
+    /// already in memory, parsing (and all later reads from the Replay) won't block on IO, so
+    /// there's no need for something like `spawn_blocking`.
+    ///
+    /// broodrep deliberately has no async parsing API. Locating and reading sections takes many
+    /// small seeks and reads, and awaiting each of those would cost more than reading the whole
+    /// file up front, since replays are rarely more than a few MB.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Result<Self, BroodrepError> {
+        Self::new(Cursor::new(bytes.into()))
+    }
+}
+
+impl<'a> Replay<Cursor<&'a [u8]>> {
+    /// Parses a replay that is already in memory with default settings, borrowing its bytes.
+    ///
+    /// This works with anything that can be borrowed as a byte slice, including memory-mapped
+    /// files (e.g. from the `memmap2` crate), which avoids copying the file into memory up front.
+    /// Note that mapping a file is only safe as long as nothing else modifies or truncates it while
+    /// the mapping exists, which includes the lifetime of the Replay. Modifications will show up
+    /// as corrupt data, and truncation can crash the process on some platforms.
+    ///
+    /// broodrep doesn't map files itself. Creating a mapping is `unsafe` because only the caller
+    /// can know whether the file might change underneath it, so that decision (and the choice of
+    /// mapping crate) is left to the caller, with this accepting the result.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, BroodrepError> {
+        Self::new(Cursor::new(bytes))
+    }
+}
+
 impl<R: Read + Seek> Replay<R> {
     /// Creates a new Replay by parsing data from a [Read] implementation with default settings for
     /// reading.
     pub fn new(reader: R) -> Result<Self, BroodrepError> {
-        Self::new_with_decompression_config(reader, DecompressionConfig::default())
+        Self::new_with_config(reader, ParseConfig::default())
     }
 
-    // TODO(tec27): Would probably be nice to be able to specify limits for the file as a whole as
-    // well
     /// Creates a new Replay by parsing data from a [Read] implementation with specified settings
     /// for reading. Note that the limits specified will apply to each chunk individually, rather
-    /// than to the entire replay collectively.
+    /// than to the entire replay collectively, except for
+    /// [max_total_decompressed_size](DecompressionConfig::max_total_decompressed_size).
     pub fn new_with_decompression_config(
-        mut reader: R,
+        reader: R,
         config: DecompressionConfig,
     ) -> Result<Self, BroodrepError> {
+        Self::new_with_config(
+            reader,
+            ParseConfig {
+                decompression: config,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a new Replay by parsing data from a [Read] implementation with the specified
+    /// [ParseConfig]. Note that the decompression limits specified will apply to each chunk
+    /// individually, rather than to the entire replay collectively, except for
+    /// [max_total_decompressed_size](DecompressionConfig::max_total_decompressed_size).
+    pub fn new_with_config(
+        mut reader: R,
+        parse_config: ParseConfig,
+    ) -> Result<Self, BroodrepError> {
+        let config = parse_config.decompression;
+        let stream_len = reader.seek(SeekFrom::End(0))?;
         let format = Self::detect_format(&mut reader)?;
 
         reader.seek(SeekFrom::Start(0))?;
         // First section is just the magic bytes, we just sanity check it and then skip it since
         // we already know the format from detect_format()
-        let header = Self::read_section_header(&mut reader)?;
+        let header = Self::read_section_header(&mut reader)
+            .map_err(|e| truncated_header(e, "truncated header: magic chunk"))?;
         if header.num_chunks != 1 {
             return Err(BroodrepError::MalformedHeader("invalid magic chunk"));
         }
-        let size = reader.read_u32::<LE>()?;
+        let size = reader
+            .read_u32::<LE>()
+            .map_err(|e| truncated_header(e.into(), "truncated header: magic chunk size"))?;
         if size != 4 {
             return Err(BroodrepError::MalformedHeader("invalid magic chunk size"));
         }
         // Magic bytes (note we've already checked this when detecting the format, so we don't need
         // to repeat that step here)
-        reader.read_u32::<LE>()?;
+        reader
+            .read_u32::<LE>()
+            .map_err(|e| truncated_header(e.into(), "truncated header: magic bytes"))?;
         if format == ReplayFormat::Modern121 {
             // This is the offset of the first section after the "legacy" sections, I guess as a
             // way to be able to skip them easily? In older formats, this offset is not present
             // (even though the Modern non-1.21 version does have other sections)
-            reader.read_u32::<LE>()?;
+            reader
+                .read_u32::<LE>()
+                .map_err(|e| truncated_header(e.into(), "truncated header: section offset"))?;
         }
 
         let mut section_offsets = HashMap::new();
 
         section_offsets.insert(ReplaySection::Header, reader.stream_position()?);
-        let replay_header =
-            Self::read_legacy_section(&mut reader, format, config, Some(SIZE_HEADER))?;
-        let replay_header = Self::parse_replay_header(&replay_header)?;
+        let mut decompressed_total = 0;
+        let mut scratch = Vec::new();
+        let replay_header = Self::read_legacy_section(
+            &mut reader,
+            stream_len,
+            format,
+            config,
+            Some(SIZE_HEADER),
+            &mut decompressed_total,
+            &mut scratch,
+        )
+        .map_err(|e| truncated_header(e, "truncated header: header section"))?;
+        let replay_header = parse_replay_header_with_encodings(
+            &replay_header,
+            parse_config.string_encoding,
+            &parse_config.fallback_encodings,
+        )?;
+        if parse_config.strict {
+            replay_header.check_known_values()?;
+        }
 
+        let duplicate_sections = parse_config.duplicate_sections;
+        // The section we were in the middle of skipping when the scan below stopped, if any
+        let mut incomplete_section = None;
+        let mut trailing_data = None;
         let r = || -> Result<(), BroodrepError> {
             // NOTE(tec27): Dynamically sized legacy sections (commands, map data) have a section
             // before them that specifies their total uncompressed size, so we need to effectively
             // skip 2 sections for those
-            Self::skip_legacy_section(&mut reader)?;
+            Self::skip_legacy_section(&mut reader, stream_len)?;
             section_offsets.insert(ReplaySection::Commands, reader.stream_position()?);
-            Self::skip_legacy_section(&mut reader)?;
+            incomplete_section = Some(ReplaySection::Commands);
+            Self::skip_legacy_section(&mut reader, stream_len)?;
+            incomplete_section = None;
 
-            Self::skip_legacy_section(&mut reader)?;
+            Self::skip_legacy_section(&mut reader, stream_len)?;
             section_offsets.insert(ReplaySection::MapData, reader.stream_position()?);
-            Self::skip_legacy_section(&mut reader)?;
+            incomplete_section = Some(ReplaySection::MapData);
+            Self::skip_legacy_section(&mut reader, stream_len)?;
+            incomplete_section = None;
 
             section_offsets.insert(ReplaySection::PlayerNames, reader.stream_position()?);
             // TODO(tec27): Probably we should read this here and update the header player names as
             // needed
-            Self::skip_legacy_section(&mut reader)?;
-
-            // Modern sections
-            if format != ReplayFormat::Legacy {
-                loop {
-                    let mut section_id = [0u8; 4];
-                    reader.read_exact(&mut section_id)?;
-
-                    let section: ReplaySection = section_id.into();
-                    if section_offsets.contains_key(&section) {
-                        // TODO(tec27): Should we actually handle this instead? No SC:R replay should
-                        // ever have this but other clients might
-                        return Err(BroodrepError::DuplicateSection(section));
+            incomplete_section = Some(ReplaySection::PlayerNames);
+            Self::skip_legacy_section(&mut reader, stream_len)?;
+            incomplete_section = None;
+
+            // Modern sections. Legacy replays shouldn't have any, but some non-standard ones carry
+            // extra data after the legacy sections, so we treat it the same way to avoid
+            // dropping it
+            let is_legacy = format == ReplayFormat::Legacy;
+            loop {
+                let section_start = reader.stream_position()?;
+                if is_legacy && section_start < stream_len {
+                    // Trailing data in legacy replays isn't guaranteed to be structured like modern
+                    // sections, so until this has been read as one, it's kept as unparsed data
+                    // rather than failing the entire replay
+                    trailing_data = Some(section_start..stream_len);
+                }
+
+                let mut section_id = [0u8; 4];
+                reader.read_exact(&mut section_id)?;
+
+                let section: ReplaySection = section_id.into();
+                let mut keep = true;
+                if section_offsets.contains_key(&section) {
+                    if is_legacy {
+                        break;
                     }
-                    section_offsets.insert(section, reader.stream_position()?);
-                    let size = reader.read_u32::<LE>()?;
-                    reader.seek(SeekFrom::Current(size as i64))?;
+                    match duplicate_sections {
+                        DuplicateSectionPolicy::Error => {
+                            return Err(BroodrepError::DuplicateSection(section));
+                        }
+                        DuplicateSectionPolicy::KeepFirst => keep = false,
+                        DuplicateSectionPolicy::KeepLast => {}
+                    }
+                }
+                let offset = reader.stream_position()?;
+                if keep && !is_legacy {
+                    section_offsets.insert(section, offset);
+                    incomplete_section = Some(section);
                 }
+                let size = reader.read_u32::<LE>()?;
+                Self::check_remaining(&mut reader, stream_len, size)?;
+                if is_legacy {
+                    section_offsets.insert(section, offset);
+                    trailing_data = None;
+                }
+                reader.seek(SeekFrom::Current(size as i64))?;
+                incomplete_section = None;
             }
 
             Ok(())
@@ -141,22 +388,117 @@ impl<R: Read + Seek> Replay<R> {
             Ok(_) => {}
             // Eof after the header is "ok", other sections are non-essential
             Err(BroodrepError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {}
+            // A section extending past the end of the file is treated the same as a truncated file
+            // here, reading that section will produce an error if it's attempted later
+            Err(BroodrepError::MalformedSection(_)) => {}
             Err(e) => return Err(e),
         }
 
         Ok(Replay {
             inner: reader,
             decompression_config: config,
+            command_decoders: CommandDecoderRegistry::new(),
+            command_parse_config: parse_config.commands,
+            string_encoding: parse_config.string_encoding,
+            fallback_encodings: parse_config.fallback_encodings,
+            stream_len,
             format,
             section_offsets,
+            incomplete_section,
+            trailing_data,
+            decompressed_total,
+            scratch,
             header: replay_header,
         })
     }
 
+    /// Creates a Replay from a [ReplaySummary] previously obtained from [Replay::summary], and a
+    /// reader over the same replay file. This avoids needing to parse the header and locate the
+    /// file's sections again, which makes it cheap to drop a Replay (e.g. with
+    /// [Replay::into_inner]) and reconstruct it later. The reader can be at any position, since
+    /// Replay always seeks before reading.
+    ///
+    /// The reader is checked to be the same size and format as the summarized replay, but the rest
+    /// of its contents are not verified. Any decoders set with [Replay::set_command_decoders] are
+    /// not part of the summary and will need to be set again.
+    pub fn from_parts(mut reader: R, summary: ReplaySummary) -> Result<Self, BroodrepError> {
+        let stream_len = reader.seek(SeekFrom::End(0))?;
+        if stream_len != summary.stream_len {
+            return Err(BroodrepError::MalformedHeader(
+                "replay size does not match the summary",
+            ));
+        }
+        if Self::detect_format(&mut reader)? != summary.format {
+            return Err(BroodrepError::MalformedHeader(
+                "replay format does not match the summary",
+            ));
+        }
+
+        Ok(Replay {
+            inner: reader,
+            decompression_config: summary.decompression_config,
+            command_decoders: CommandDecoderRegistry::new(),
+            command_parse_config: summary.command_parse_config,
+            string_encoding: summary.string_encoding,
+            fallback_encodings: summary.fallback_encodings,
+            stream_len,
+            format: summary.format,
+            section_offsets: summary.section_offsets,
+            incomplete_section: summary.incomplete_section,
+            trailing_data: summary.trailing_data,
+            decompressed_total: 0,
+            scratch: Vec::new(),
+            header: summary.header,
+        })
+    }
+
+    /// Returns a [ReplaySummary] containing everything that was determined while parsing this
+    /// replay, which can be cached and later passed to [Replay::from_parts].
+    pub fn summary(&self) -> ReplaySummary {
+        ReplaySummary {
+            format: self.format,
+            header: self.header.clone(),
+            decompression_config: self.decompression_config,
+            command_parse_config: self.command_parse_config,
+            string_encoding: self.string_encoding,
+            fallback_encodings: self.fallback_encodings.clone(),
+            stream_len: self.stream_len,
+            section_offsets: self.section_offsets.clone(),
+            incomplete_section: self.incomplete_section,
+            trailing_data: self.trailing_data.clone(),
+        }
+    }
+
     pub fn into_inner(self) -> R {
         self.inner
     }
 
+    /// Reads and parses every section of the replay up front, returning a [ResolvedReplay] that no
+    /// longer needs the reader. This is useful for sharing a replay across threads, but keeps all
+    /// of its data in memory (see [ResolvedReplay] for more details).
+    ///
+    /// Any errors that would be encountered reading or parsing the sections are returned here,
+    /// rather than when the data is accessed later.
+    pub fn resolve(mut self) -> Result<ResolvedReplay, BroodrepError> {
+        let commands = self.commands()?;
+        let present = self.section_offsets.keys().copied().collect::<Vec<_>>();
+        let mut sections = HashMap::with_capacity(present.len());
+        for section in present {
+            if let Some(data) = self.get_raw_section(section)? {
+                sections.insert(section, data);
+            }
+        }
+
+        Ok(ResolvedReplay {
+            format: self.format,
+            header: self.header,
+            sections,
+            commands,
+            string_encoding: self.string_encoding,
+            fallback_encodings: self.fallback_encodings,
+        })
+    }
+
     pub fn format(&self) -> ReplayFormat {
         self.format
     }
@@ -169,6 +511,33 @@ impl<R: Read + Seek> Replay<R> {
         self.header.frames
     }
 
+    /// Returns how long the game lasted in real time, based on its [GameSpeed].
+    pub fn duration(&self) -> std::time::Duration {
+        self.duration_at_speed(self.game_speed())
+    }
+
+    /// Returns how long the game would have lasted in real time if it had been played at `speed`.
+    /// This is useful for comparing games played at different speeds (e.g. by converting them all
+    /// to their [Fastest](GameSpeed::Fastest) equivalent).
+    pub fn duration_at_speed(&self, speed: GameSpeed) -> std::time::Duration {
+        speed.time_per_step() * self.frames()
+    }
+
+    /// Returns the real time elapsed from the start of the game until `frame`, based on its
+    /// [GameSpeed]. Nearly all competitive games are played on [Fastest](GameSpeed::Fastest), where
+    /// each frame takes 42ms (about 23.8 frames per second).
+    pub fn frame_to_time(&self, frame: u32) -> std::time::Duration {
+        self.game_speed().time_per_step() * frame
+    }
+
+    /// Returns the frame that was being executed `time` after the start of the game, based on its
+    /// [GameSpeed]. This is the inverse of [Replay::frame_to_time] (rounding down to the nearest
+    /// frame), and is not limited to the length of the game.
+    pub fn time_to_frame(&self, time: std::time::Duration) -> u32 {
+        let frames = time.as_nanos() / self.game_speed().time_per_step().as_nanos();
+        frames.min(u32::MAX as u128) as u32
+    }
+
     /// Returns the time the game started at, as dictated by the game host. Note that this is
     /// technically the game seed and not a timestamp (it just happens to use a timestamp), so this
     /// isn't *guaranteed* to be an accurate time (but in practice it is).
@@ -203,6 +572,18 @@ impl<R: Read + Seek> Replay<R> {
         self.header.game_sub_type
     }
 
+    /// Returns the player (or observer) that issued commands with the specified
+    /// [player_id](Command::player_id) (see [ReplayHeader::player_for_command]).
+    pub fn player_for_command(&self, command_player_id: u8) -> Option<&Player> {
+        self.header.player_for_command(command_player_id)
+    }
+
+    /// Returns how the players in the game were split into teams (see
+    /// [ReplayHeader::team_layout]).
+    pub fn team_layout(&self) -> TeamLayout {
+        self.header.team_layout()
+    }
+
     pub fn host_name(&self) -> &str {
         &self.header.host_name
     }
@@ -214,6 +595,22 @@ impl<R: Read + Seek> Replay<R> {
             .find(|p| p.name == self.header.host_name)
     }
 
+    /// Returns the standard Brood War color (see [STANDARD_PLAYER_COLORS]) of the player in the
+    /// specified slot, or [None] if `slot_id` isn't a valid slot.
+    ///
+    /// Colors chosen in the lobby are stored in the [CustomColors](ReplaySection::CustomColors)
+    /// section, whose layout isn't known yet. Since the standard color may not be the one the
+    /// player actually had, this also returns [None] for every slot if that section is present.
+    pub fn player_color(&self, slot_id: u16) -> Option<PlayerColor> {
+        if self
+            .section_offsets
+            .contains_key(&ReplaySection::CustomColors)
+        {
+            return None;
+        }
+        STANDARD_PLAYER_COLORS.get(slot_id as usize).copied()
+    }
+
     pub fn players(&self) -> impl Iterator<Item = &Player> {
         self.header.players()
     }
@@ -226,6 +623,249 @@ impl<R: Read + Seek> Replay<R> {
         &self.header.slots
     }
 
+    /// Returns the number of slots that were filled in the game, including observers and computer
+    /// players.
+    pub fn occupied_slot_count(&self) -> usize {
+        self.header.slots.iter().filter(|p| !p.is_empty()).count()
+    }
+
+    /// Returns the number of non-observer players in the game that were controlled by a human.
+    pub fn human_player_count(&self) -> usize {
+        self.players()
+            .filter(|p| p.player_type == PlayerType::Human)
+            .count()
+    }
+
+    /// Returns true if more than one human took part in the game (including observers), i.e. it
+    /// wasn't a single player game against computers.
+    pub fn is_multiplayer(&self) -> bool {
+        self.header
+            .slots
+            .iter()
+            .filter(|p| !p.is_empty() && p.player_type == PlayerType::Human)
+            .count()
+            > 1
+    }
+
+    /// Returns true if any of the players in the game were controlled by the computer.
+    pub fn has_computer_players(&self) -> bool {
+        self.players().any(|p| {
+            matches!(
+                p.player_type,
+                PlayerType::Computer | PlayerType::ComputerControlled
+            )
+        })
+    }
+
+    /// Returns the total size of the replay file, in bytes.
+    pub fn file_size(&self) -> u64 {
+        self.stream_len
+    }
+
+    /// Returns every section present in the replay (including custom sections), in no particular
+    /// order. See [Replay::sections_ordered] for the order they appear in the file.
+    pub fn sections(&self) -> impl Iterator<Item = ReplaySection> + '_ {
+        self.section_offsets.keys().copied()
+    }
+
+    /// Returns the location (as a range of offsets from the beginning of the file) of any data at
+    /// the end of a legacy replay that couldn't be read as a section, or [None] if there isn't any.
+    ///
+    /// Legacy replays normally end after their [PlayerNames](ReplaySection::PlayerNames) section,
+    /// but some non-standard ones carry extra data. Data that is laid out like a modern section
+    /// is available as a [Custom](ReplaySection::Custom) section, while anything after that
+    /// (e.g. a repeated section, or bytes that don't form a valid section) is reported here
+    /// instead of being ignored.
+    pub fn trailing_data(&self) -> Option<Range<u64>> {
+        self.trailing_data.clone()
+    }
+
+    /// Returns whether `section` is present in the replay. Note that a section that is present may
+    /// still fail to be read, if the file was truncated partway through it.
+    pub fn has_section(&self, section: ReplaySection) -> bool {
+        self.section_offsets.contains_key(&section)
+    }
+
+    /// Returns the IDs of every [Custom](ReplaySection::Custom) section in the replay, in the order
+    /// they appear in the file. These are sections broodrep doesn't recognize, such as those
+    /// written by third-party clients.
+    pub fn custom_section_ids(&self) -> Vec<[u8; 4]> {
+        self.sections_ordered()
+            .into_iter()
+            .filter_map(|(section, _)| match section {
+                ReplaySection::Custom(tag) => Some(tag.0),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns every section present in the replay (including custom sections), along with its
+    /// offset from the beginning of the file, in the order they appear in the file. For legacy
+    /// sections, the offset is that of the section's first chunk header. For modern sections, it's
+    /// the offset of the section's size, which directly follows its 4-byte tag.
+    pub fn sections_ordered(&self) -> Vec<(ReplaySection, u64)> {
+        let mut offsets = self
+            .section_offsets
+            .iter()
+            .map(|(&section, &offset)| (section, offset))
+            .collect::<Vec<_>>();
+        offsets.sort_by_key(|&(_, offset)| offset);
+        offsets
+    }
+
+    /// Returns the ID of every modern section in the replay (both known and custom), along with its
+    /// offset (as in [Replay::sections_ordered]), in the order they appear in the file. The legacy
+    /// sections that precede them have no ID and are not included.
+    ///
+    /// Clients are free to write modern sections in any order, so the layout (along with which
+    /// sections are present) can help identify the client that wrote a replay.
+    pub fn section_layout(&self) -> Vec<([u8; 4], u64)> {
+        self.sections_ordered()
+            .into_iter()
+            .filter_map(|(section, offset)| Some((section.tag()?.0, offset)))
+            .collect()
+    }
+
+    /// Returns the on-disk (compressed) size of each section present in the replay, in the order
+    /// they appear in the file. Sizes are computed from the distance to the next section (or the
+    /// end of the file, for the last one), so they include each section's headers, as well as
+    /// anything between the sections (such as the size prefixes before [ReplaySection::Commands]
+    /// and [ReplaySection::MapData], which are counted towards the section before them).
+    pub fn size_breakdown(&self) -> Vec<(ReplaySection, u64)> {
+        let offsets = self.sections_ordered();
+
+        let ends = offsets
+            .iter()
+            .skip(1)
+            .map(|&(_, offset)| offset)
+            .chain(std::iter::once(self.stream_len));
+        offsets
+            .iter()
+            .zip(ends)
+            .map(|(&(section, offset), end)| (section, end.saturating_sub(offset)))
+            .collect()
+    }
+
+    /// Returns which of the sections every replay should contain ([Header](ReplaySection::Header),
+    /// [Commands](ReplaySection::Commands), [MapData](ReplaySection::MapData), and
+    /// [PlayerNames](ReplaySection::PlayerNames)) are missing or cut short, in file order.
+    ///
+    /// Parsing a replay only requires the header to be intact (parsing will fail otherwise, so
+    /// [Header](ReplaySection::Header) will never be returned here). If the file is truncated after
+    /// that, the remaining sections are silently treated as absent, which means e.g.
+    /// [Replay::commands] will return an empty list for a replay whose commands were cut off. This
+    /// can be used to tell that apart from a game where nothing happened.
+    pub fn missing_required_sections(&self) -> Vec<ReplaySection> {
+        [
+            ReplaySection::Header,
+            ReplaySection::Commands,
+            ReplaySection::MapData,
+            ReplaySection::PlayerNames,
+        ]
+        .into_iter()
+        .filter(|s| !self.section_offsets.contains_key(s) || self.incomplete_section == Some(*s))
+        .collect()
+    }
+
+    /// Verifies the checksums stored in the headers of the legacy sections
+    /// ([Header](ReplaySection::Header), [Commands](ReplaySection::Commands),
+    /// [MapData](ReplaySection::MapData), and [PlayerNames](ReplaySection::PlayerNames)), which
+    /// are a CRC-32 of each section's decompressed data. Modern sections don't have a checksum, so
+    /// they aren't included.
+    ///
+    /// This decompresses all of the legacy sections, so it counts towards
+    /// [max_total_decompressed_size](DecompressionConfig::max_total_decompressed_size).
+    pub fn verify_checksums(&mut self) -> Result<ChecksumReport, BroodrepError> {
+        let mut sections = Vec::new();
+        for section in [
+            ReplaySection::Header,
+            ReplaySection::Commands,
+            ReplaySection::MapData,
+            ReplaySection::PlayerNames,
+        ] {
+            let Some(&offset) = self.section_offsets.get(&section) else {
+                continue;
+            };
+            self.inner.seek(SeekFrom::Start(offset))?;
+            let expected = match Self::read_section_header(&mut self.inner) {
+                Ok(header) => header.checksum,
+                Err(BroodrepError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    sections.push(SectionChecksum {
+                        section,
+                        expected: None,
+                        actual: None,
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            self.inner.seek(SeekFrom::Start(offset))?;
+            let data = Self::read_legacy_section(
+                &mut self.inner,
+                self.stream_len,
+                self.format,
+                self.decompression_config,
+                section.size_hint(),
+                &mut self.decompressed_total,
+                &mut self.scratch,
+            );
+            let actual = match data {
+                Ok(data) => Some(crc32fast::hash(&data)),
+                // Cut off partway through, which is a mismatch rather than a failure to verify
+                Err(BroodrepError::MalformedSection(_)) => None,
+                Err(BroodrepError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+            sections.push(SectionChecksum {
+                section,
+                expected: Some(expected),
+                actual,
+            });
+        }
+
+        Ok(ChecksumReport { sections })
+    }
+
+    /// Returns a fingerprint identifying the game this replay contains, which can be used to find
+    /// duplicate replays. It only depends on the decompressed contents of the replay, so files that
+    /// differ in how they were compressed (e.g. after being re-saved) will have the same
+    /// fingerprint.
+    ///
+    /// The fingerprint is the SHA-256 hash of the bytes `broodrep-fingerprint-v1`, followed by the
+    /// following for each of the [Header](ReplaySection::Header),
+    /// [MapData](ReplaySection::MapData), and [Commands](ReplaySection::Commands) sections, in that
+    /// order:
+    ///
+    /// - A single byte that is 1 if the section is present and 0 if it isn't
+    /// - If present, the length of the section's decompressed data as a little-endian `u32`,
+    ///   followed by the data itself
+    ///
+    /// This will not change between versions of broodrep (a different algorithm would use a
+    /// different prefix). Note that replays of the same game saved by different players are not
+    /// guaranteed to have the same fingerprint.
+    pub fn fingerprint(&mut self) -> Result<[u8; 32], BroodrepError> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"broodrep-fingerprint-v1");
+        for section in [
+            ReplaySection::Header,
+            ReplaySection::MapData,
+            ReplaySection::Commands,
+        ] {
+            match self.get_raw_section(section)? {
+                Some(data) => {
+                    hasher.update([1]);
+                    hasher.update((data.len() as u32).to_le_bytes());
+                    hasher.update(&data);
+                }
+                None => hasher.update([0]),
+            }
+        }
+        Ok(hasher.finalize().into())
+    }
+
     /// Returns the raw bytes of a given replay section, or [None] if not present in the replay
     /// file. The bytes will be decompressed if it is a section with known compression.
     pub fn get_raw_section(
@@ -240,6 +880,7 @@ impl<R: Read + Seek> Replay<R> {
         if section.is_modern() {
             self.inner.seek(SeekFrom::Start(offset))?;
             let size = self.inner.read_u32::<LE>()?;
+            Self::check_remaining(&mut self.inner, self.stream_len, size)?;
             let mut data = vec![0; size as usize];
             self.inner.read_exact(&mut data)?;
             Ok(Some(data))
@@ -247,14 +888,61 @@ impl<R: Read + Seek> Replay<R> {
             self.inner.seek(SeekFrom::Start(offset))?;
             let bytes = Self::read_legacy_section(
                 &mut self.inner,
+                self.stream_len,
                 self.format,
                 self.decompression_config,
                 section.size_hint(),
+                &mut self.decompressed_total,
+                &mut self.scratch,
             )?;
             Ok(Some(bytes))
         }
     }
 
+    /// Like [Replay::get_raw_section], but returns [BroodrepError::MissingSection] if the section
+    /// isn't present in the replay file.
+    pub fn require_section(&mut self, section: ReplaySection) -> Result<Vec<u8>, BroodrepError> {
+        self.get_raw_section(section)?
+            .ok_or(BroodrepError::MissingSection(section))
+    }
+
+    /// Returns the locations of the chunks that make up a section's data in the file, in the order
+    /// they are stored. If the section isn't present, the result will be empty.
+    ///
+    /// Legacy sections (e.g. [Header](ReplaySection::Header), [Commands](ReplaySection::Commands))
+    /// are split into one or more chunks that are each compressed separately (with PKWare implode
+    /// for pre-1.18 replays, or zlib for newer ones, although chunks may also be stored
+    /// uncompressed). Modern sections are instead stored as a single blob following their size,
+    /// so they will always return a single chunk containing the entire section.
+    pub fn section_chunk_layout(
+        &mut self,
+        section: ReplaySection,
+    ) -> Result<Vec<ChunkInfo>, BroodrepError> {
+        let offset = match self.section_offsets.get(&section) {
+            Some(o) => *o,
+            None => return Ok(Vec::new()),
+        };
+
+        self.inner.seek(SeekFrom::Start(offset))?;
+        if section.is_modern() {
+            let size = self.inner.read_u32::<LE>()?;
+            Self::check_remaining(&mut self.inner, self.stream_len, size)?;
+            Ok(vec![ChunkInfo {
+                offset: offset + 4,
+                compressed_size: size,
+            }])
+        } else {
+            Self::read_chunk_layout(&mut self.inner, self.stream_len)
+        }
+    }
+
+    /// Returns whether the replay was recorded on ShieldBattery (i.e. whether it has a
+    /// ShieldBattery section). Unlike [Replay::get_shieldbattery_section], this doesn't need to
+    /// read or parse the section.
+    pub fn is_shieldbattery(&self) -> bool {
+        self.has_section(ReplaySection::ShieldBattery)
+    }
+
     /// Returns the parsed ShieldBattery data section, if present.
     pub fn get_shieldbattery_section(
         &mut self,
@@ -266,27 +954,472 @@ impl<R: Read + Seek> Replay<R> {
         Ok(Some(shieldbattery::parse_shieldbattery_section(&data)?))
     }
 
+    /// Returns everyone who took part in the game (players and observers, including computers),
+    /// along with their ShieldBattery user ID if the replay has a ShieldBattery section.
+    ///
+    /// The header's 12 slots have room for every participant SC:R allows (8 players and 4
+    /// observers), so these are just the filled header slots: no other section lists additional
+    /// participants. Note that ShieldBattery only records user IDs for the first 8 slots, so
+    /// observers in later slots won't have one.
+    pub fn all_participants(&mut self) -> Result<Vec<Participant>, BroodrepError> {
+        let shieldbattery = self.get_shieldbattery_section()?;
+        Ok(self
+            .header
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !p.is_empty())
+            .map(|(i, p)| Participant {
+                player: p.clone(),
+                shieldbattery_user_id: shieldbattery
+                    .as_ref()
+                    .and_then(|data| data.user_id_for_slot(i)),
+            })
+            .collect())
+    }
+
+    /// Returns the flags from the Bfix section (the game bug fixes that were enabled when the game
+    /// was played), if present.
+    pub fn bugfixes(&mut self) -> Result<Option<BugFixFlags>, BroodrepError> {
+        let data = match self.get_raw_section(ReplaySection::Bfix)? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(BugFixFlags::parse(&data)?))
+    }
+
+    /// Sets the decoders that will be used for custom commands (e.g. those added by modded
+    /// clients) when parsing the command stream. See [CommandDecoderRegistry].
+    pub fn set_command_decoders(&mut self, registry: CommandDecoderRegistry) {
+        self.command_decoders = registry;
+    }
+
+    /// Returns all of the commands issued by players during the game, in the order they were
+    /// executed. If the replay has no Commands section, the result will be empty.
+    pub fn commands(&mut self) -> Result<Vec<Command>, BroodrepError> {
+        let data = match self.get_raw_section(ReplaySection::Commands)? {
+            Some(d) => d,
+            None => return Ok(Vec::new()),
+        };
+        Ok(commands::parse_commands(
+            &data,
+            &self.command_decoders,
+            &self.command_parse_config,
+        )?)
+    }
+
+    /// Returns an iterator over the frames of the command stream, which decodes the commands for
+    /// each frame as it is reached. This is cheaper than [Replay::commands] when only part of the
+    /// command stream is needed, e.g. when searching for the first occurrence of a command. Note
+    /// that the Commands section itself still needs to be read and decompressed in full.
+    ///
+    /// If the replay has no Commands section, the iterator will be empty.
+    pub fn command_frames(&mut self) -> Result<CommandFrames<'_>, BroodrepError> {
+        let data = self
+            .get_raw_section(ReplaySection::Commands)?
+            .unwrap_or_default();
+        Ok(CommandFrames::new(
+            data,
+            &self.command_decoders,
+            self.command_parse_config,
+        ))
+    }
+
+    /// Returns a timeline of the units each player produced, keyed by the player's ID in the
+    /// command stream. Each player's timeline contains one sample for every `interval` frames of
+    /// the game (with the last sample taken at the final frame of the game), holding cumulative
+    /// counts of every unit type built, trained, or morphed up to that point. Players that never
+    /// produced anything will not be present.
+    ///
+    /// Note that these are counts of units *produced*, not units alive at that point: unit deaths
+    /// can't be determined from the replay without simulating the game. Counts are also based on
+    /// the commands players issued, so an order that failed (e.g. due to lacking resources) or was
+    /// later canceled will still be counted.
+    pub fn unit_production_timeline(
+        &mut self,
+        interval: u32,
+    ) -> Result<HashMap<u8, Vec<ProductionSample>>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::unit_production_timeline(
+            &commands,
+            self.frames(),
+            interval,
+        ))
+    }
+
+    /// Returns an estimate of the minerals and gas each player spent during the game, keyed by the
+    /// player's ID in the command stream. Spending is calculated from the build, train, morph,
+    /// research, and upgrade commands each player issued, using the standard melee costs. Players
+    /// that never spent anything will not be present.
+    ///
+    /// This is an approximation: it doesn't account for income, so orders that failed because the
+    /// player couldn't afford them (or had no larva, a full queue, etc.) are still counted. Cancels
+    /// are only accounted for if [SpendingConfig::refund_cancels] is set, see its documentation
+    /// for how they are matched to orders.
+    pub fn resource_spending(
+        &mut self,
+        config: &SpendingConfig,
+    ) -> Result<HashMap<u8, PlayerSpending>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::resource_spending(
+            &commands,
+            self.frames(),
+            config,
+        ))
+    }
+
+    /// Checks the command stream for signs that the replay was spliced together from multiple
+    /// games (or otherwise edited), returning the spans of frames that look suspicious, ordered by
+    /// their starting frame. An empty result means nothing suspicious was found.
+    ///
+    /// This is heuristic, and only looks for things that the game itself should never produce
+    /// (frames going backwards, commands from players who have left, commands past the end of the
+    /// game) plus very long stretches without any commands. It's tuned to avoid false positives,
+    /// so a carefully edited replay can still go undetected, but anything it reports is worth
+    /// looking into. Note that games against computer players may legitimately have long periods
+    /// without commands, as computers don't issue any.
+    pub fn detect_splicing(&mut self) -> Result<Vec<SuspiciousSpan>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::detect_splicing(&commands, self.frames()))
+    }
+
+    /// Returns how many units each player selected at a time, keyed by the player's ID in the
+    /// command stream. Each player's map contains the number of select commands issued for every
+    /// selection size (e.g. a player that selected a single unit 10 times would have an entry of
+    /// `1 => 10`), which is useful as a proxy for army and control group sizes.
+    ///
+    /// Only commands that replace the current selection are counted, as the size of the resulting
+    /// selection can't be known for commands that add or remove units. Replays also only record
+    /// the units selected, not their types, so determining *what* was selected would require
+    /// simulating the game.
+    pub fn selection_sizes(&mut self) -> Result<HashMap<u8, BTreeMap<usize, u32>>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::selection_sizes(&commands))
+    }
+
+    /// Returns how many times each type of command appears in the command stream. Commands that
+    /// broodrep doesn't decode are grouped by their ID, as [CommandType::Unknown] (and similarly for
+    /// [CommandType::Custom]). See [Replay::command_stats_by_player] for per-player counts.
+    pub fn command_stats(&mut self) -> Result<HashMap<CommandType, usize>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::command_stats(&commands))
+    }
+
+    /// Returns all of the commands issued during the game, grouped by the ID of the player that
+    /// issued them (see [Command::player_id]). Each player's commands are in the order they were
+    /// executed. Players that issued no commands will not be present.
+    pub fn commands_by_player(&mut self) -> Result<HashMap<u8, Vec<Command>>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::commands_by_player(commands))
+    }
+
+    /// Returns how many times each type of command was issued by each player, keyed by the
+    /// player's ID in the command stream. See [Replay::command_stats] for details.
+    pub fn command_stats_by_player(
+        &mut self,
+    ) -> Result<HashMap<u8, HashMap<CommandType, usize>>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::command_stats_by_player(&commands))
+    }
+
+    /// Returns the build order of each player, keyed by the player's ID in the command stream:
+    /// every unit they built, trained, or morphed into (including Zerg units morphed from larvae
+    /// and buildings morphed into other buildings), in the order they were ordered. Players that
+    /// never produced anything will not be present.
+    ///
+    /// Like [Replay::unit_production_timeline], this is based on the commands players issued, so
+    /// orders that failed or were later canceled are still included.
+    pub fn build_orders(&mut self) -> Result<HashMap<u8, Vec<BuildStep>>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::build_orders(
+            &commands,
+            self.game_speed().time_per_step(),
+        ))
+    }
+
+    /// Returns the chat messages sent during the game, in the order they were sent, along with the
+    /// time each was sent at (see [ChatMessage] for the different ways times are given).
+    pub fn chat_messages(&mut self) -> Result<Vec<ChatMessage>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::chat_messages(
+            &commands,
+            self.game_speed().time_per_step(),
+        ))
+    }
+
+    /// Writes the command stream to `w` in CSV format, with one row per command. The first row is a
+    /// header, and the columns are:
+    ///
+    /// | Column         | Contents                                                             |
+    /// |----------------|----------------------------------------------------------------------|
+    /// | `frame`        | The frame the command was executed on                                |
+    /// | `real_time_s`  | The real time of `frame` in seconds, based on the game speed         |
+    /// | `player_id`    | The player's ID in the command stream ([Player::network_id])         |
+    /// | `command_type` | The [CommandType] of the command                                     |
+    /// | `unit_type`    | The unit type built, trained, morphed into, or right-clicked on      |
+    /// | `x`, `y`       | The target position of builds and right clicks                       |
+    /// | `value`        | The command's main numeric operand (see below)                       |
+    /// | `text`         | The command's text operand (see below)                               |
+    ///
+    /// `value` contains the number of units selected for selections, the target unit tag for right
+    /// clicks, the tech/upgrade ID for research, the queue entry for canceled training, the group
+    /// number for hotkeys, the sending slot for chat, and the reason code for leaving the game.
+    /// `text` contains the selected unit tags (space-separated) for selections, the
+    /// [HotkeyAction] for hotkeys, the message for chat, the new setting for latency changes, and
+    /// the hex-encoded data for sync commands and commands broodrep doesn't decode. Columns that
+    /// don't apply to a command are left empty, and fields are quoted as necessary.
+    pub fn write_commands_csv<W: Write>(&mut self, w: W) -> Result<(), BroodrepError> {
+        let commands = self.commands()?;
+        export::write_commands_csv(w, &commands, self.game_speed().time_per_step())?;
+        Ok(())
+    }
+
+    /// Returns the format, duration (see [Replay::duration]), header, players, and observers of
+    /// the replay as a single JSON object. Everything is written using the same representation as
+    /// the types' `serde` implementations, so field names are snake_case, enum values are written
+    /// using the names of their variants, and values broodrep doesn't recognize are written as
+    /// `{"Unknown": <value>}`. The object looks like:
+    ///
+    /// ```json
+    /// {
+    ///   "format": "Modern121",
+    ///   "duration_ms": 420000,
+    ///   "header": {
+    ///     "engine": "BroodWar",
+    ///     "frames": 10000,
+    ///     "start_time": 1700000000,
+    ///     "title": "game title",
+    ///     "raw_title": [103, 97, 109, 101, ...],
+    ///     ...
+    ///     "slots": [...]
+    ///   },
+    ///   "players": [
+    ///     {
+    ///       "slot_id": 0,
+    ///       "network_id": 0,
+    ///       "player_type": "Human",
+    ///       "race": "Zerg",
+    ///       "team": 1,
+    ///       "name": "player",
+    ///       "raw_name": [112, 108, 97, 121, 101, 114]
+    ///     }
+    ///   ],
+    ///   "observers": []
+    /// }
+    /// ```
+    ///
+    /// `header` is a [ReplayHeader] (including every slot), while `players` and `observers` contain
+    /// the slots returned by [Replay::players] and [Replay::observers].
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, BroodrepError> {
+        export::header_json(self.format, &self.header)
+    }
+
+    /// Returns the latency setting the game ended with, if it was changed during the game (e.g.
+    /// with the `/latency` chat command). The setting chosen in the lobby isn't stored in any part
+    /// of the replay broodrep knows how to parse, so if it was never changed this will return
+    /// [None]. See [CommandKind::SetLatency] for the individual changes.
+    pub fn turn_rate(&mut self) -> Result<Option<TurnRate>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::turn_rate(&commands))
+    }
+
+    /// Returns how often each player assigned, added to, and selected control groups, and how many
+    /// different groups they used, keyed by the player's ID in the command stream. Players that
+    /// never used a control group will not be present. Hotkey commands with an unknown action or a
+    /// group outside of 0-9 are ignored.
+    pub fn control_group_stats(&mut self) -> Result<HashMap<u8, ControlGroupStats>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::control_group_stats(&commands))
+    }
+
+    /// Returns statistics about how regularly each player took actions (see
+    /// [CommandKind::is_action]), keyed by the player's ID in the command stream. Gaps are
+    /// measured in frames between each of a player's consecutive actions, starting from their
+    /// first action, so the time spent loading and before a player first acts is not included.
+    /// Players with fewer than 2 actions will not be present.
+    ///
+    /// Humans tend to act at irregular intervals, so a very low standard deviation relative to the
+    /// mean can be a sign of automation.
+    pub fn action_cadence(&mut self) -> Result<HashMap<u8, ActionCadence>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::action_cadence(&commands))
+    }
+
+    /// Returns the average actions per minute of each player, keyed by the player's ID in the
+    /// command stream. Only commands that count as actions (see [CommandKind::is_action]) are
+    /// included, so keep-alive, sync, chat, and other non-gameplay commands are excluded. Players
+    /// that never took an action will not be present.
+    ///
+    /// Minutes are measured in real time over the full length of the game (based on its game
+    /// speed, see [GameSpeed::time_per_step]), which matches the APM shown in game.
+    pub fn apm(&mut self) -> Result<HashMap<u8, f64>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::apm(&commands, self.duration()))
+    }
+
+    /// Returns the average effective actions per minute (EAPM) of each player, keyed by the
+    /// player's ID in the command stream. This is calculated the same way as [Replay::apm], but
+    /// excludes actions that had no effect on the game, which are:
+    ///
+    /// - Repeating the previous action (e.g. selecting the same units or pressing the same hotkey
+    ///   again) within [EapmConfig::window] frames. Train and unit morph commands are exempt, as
+    ///   repeating those queues up more units.
+    /// - Selecting units, then replacing that selection (with another selection or a hotkey)
+    ///   within [EapmConfig::window] frames, without doing anything in between.
+    ///
+    /// Players that never took an action will not be present.
+    pub fn eapm(&mut self, config: &EapmConfig) -> Result<HashMap<u8, f64>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::eapm(&commands, self.duration(), config))
+    }
+
+    /// Returns every time a player left the game, in the order they left.
+    pub fn leave_events(&mut self) -> Result<Vec<LeaveEvent>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::leave_events(&commands))
+    }
+
+    /// Returns every sync command in the command stream, in the order they were executed. Comparing
+    /// these between the replays of different players in the same game can help locate where a
+    /// desync happened.
+    pub fn sync_frames(&mut self) -> Result<Vec<SyncFrame>, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::sync_frames(&commands))
+    }
+
+    /// Returns the ID (in the command stream) of the player that most likely won the game, or
+    /// [None] if it can't be determined with reasonable confidence.
+    ///
+    /// Replays don't record the result of the game, so this is inferred from the order players
+    /// left in: the first of the two players to leave is assumed to have lost. This is only
+    /// attempted for games between exactly 2 (non-observer) players on opposing sides, and [None]
+    /// will be returned if neither player left, or both left on the same frame. Note that this
+    /// can be fooled by a player leaving a game they were winning, e.g. due to a disconnect.
+    pub fn inferred_winner(&mut self) -> Result<Option<u8>, BroodrepError> {
+        let Some(players) = self.header.opposing_pair() else {
+            return Ok(None);
+        };
+        let events = self.leave_events()?;
+        Ok(analysis::inferred_winner(&events, players))
+    }
+
+    /// Returns a heuristic score for how action-packed the game was, meant for ranking replays
+    /// (higher is more intense). The score doesn't have a meaningful unit or upper bound, and
+    /// should only be compared against other scores calculated with the same config.
+    ///
+    /// The score is calculated as:
+    ///
+    /// ```text
+    /// mean APM * (1 + burstiness_weight * burstiness) * min(game length / full_length, 1)
+    /// ```
+    ///
+    /// where mean APM is averaged over every player that took at least one action (see
+    /// [Replay::apm]), and burstiness is the coefficient of variation (standard deviation / mean)
+    /// of the number of actions taken in each [window](IntensityConfig::window) of the game, so
+    /// games with big spikes in activity (e.g. large fights) score higher than ones with the same
+    /// APM spread evenly throughout.
+    pub fn intensity_score(&mut self, config: &IntensityConfig) -> Result<f64, BroodrepError> {
+        let commands = self.commands()?;
+        Ok(analysis::intensity_score(
+            &commands,
+            self.frames(),
+            self.game_speed().time_per_step(),
+            config,
+        ))
+    }
+
+    /// Returns the map the game was played on, parsed from the replay's
+    /// [MapData](ReplaySection::MapData) section, or [None] if the replay doesn't contain map data.
+    pub fn map_data(&mut self) -> Result<Option<MapData>, BroodrepError> {
+        let data = match self.get_raw_section(ReplaySection::MapData)? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(MapData::parse_with_encodings(
+            &data,
+            self.string_encoding,
+            &self.fallback_encodings,
+        )?))
+    }
+
+    /// Returns the SHA-1 hash of the map data (i.e. the decompressed CHK) the game was played on,
+    /// or [None] if the replay doesn't contain map data.
+    ///
+    /// NOTE: This is *not* the hash ladders use to identify maps, as that is computed over the map
+    /// file (the MPQ archive the CHK is stored in), which replays don't contain. Replays of the
+    /// same map will still have the same hash, however, so this can be used to match replays to
+    /// known maps once the hash for each map has been recorded from a replay. Other variants can
+    /// be computed from the raw CHK data returned by [Replay::get_raw_section].
+    pub fn map_hash(&mut self) -> Result<Option<[u8; 20]>, BroodrepError> {
+        Ok(self
+            .get_raw_section(ReplaySection::MapData)?
+            .map(|data| Sha1::digest(data).into()))
+    }
+
+    /// Returns the number of triggers in the map the game was played on, or [None] if the replay
+    /// doesn't contain map data. This is a rough measure of how complex a map's scripting is: melee
+    /// maps typically contain only the default triggers, while UMS maps may have hundreds or
+    /// thousands.
+    pub fn trigger_count(&mut self) -> Result<Option<usize>, BroodrepError> {
+        let data = match self.get_raw_section(ReplaySection::MapData)? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(map::trigger_count(&data)))
+    }
+
     fn detect_format(reader: &mut R) -> Result<ReplayFormat, BroodrepError> {
         // 1.21+ has `seRS`, before that it's `reRS`
         reader.seek(SeekFrom::Start(12))?;
         let mut magic = [0; 4];
-        reader.read_exact(&mut magic)?;
+        if let Err(e) = reader.read_exact(&mut magic) {
+            if e.kind() != std::io::ErrorKind::UnexpectedEof {
+                return Err(e.into());
+            }
+            return Err(if Self::has_magic_chunk(reader)? {
+                BroodrepError::MalformedHeader("truncated header: magic bytes")
+            } else {
+                BroodrepError::NotAReplay
+            });
+        }
 
         if magic == *b"seRS" {
             return Ok(ReplayFormat::Modern121);
         }
         if magic != *b"reRS" {
-            return Err(BroodrepError::MalformedHeader("invalid magic bytes"));
+            return Err(if Self::has_magic_chunk(reader)? {
+                BroodrepError::MalformedHeader("invalid magic bytes")
+            } else {
+                BroodrepError::NotAReplay
+            });
         }
 
         // Check compression type, newer compression type indicates 1.18+
         reader.seek(SeekFrom::Current(12))?; // offset 28
-        let byte = reader.read_u8()?;
-        if byte == 0x78 {
-            Ok(ReplayFormat::Modern)
-        } else {
-            // TODO(tec27): Make sure it's within the valid range
-            Ok(ReplayFormat::Legacy)
+        let mut compression_header = [0; 2];
+        reader
+            .read_exact(&mut compression_header)
+            .map_err(|e| truncated_header(e.into(), "truncated header: compression type"))?;
+        match compression_header {
+            // zlib, with a 32KB window (which is all SC:R writes)
+            [0x78, _] => Ok(ReplayFormat::Modern),
+            // PKWare implode: binary (0) or ASCII (1) literals, followed by the dictionary size
+            [0 | 1, 4..=6] => Ok(ReplayFormat::Legacy),
+            _ => Err(BroodrepError::MalformedHeader("invalid compression type")),
+        }
+    }
+
+    /// Returns whether the data has the structure every replay starts with: a section containing a
+    /// single 4 byte chunk (the magic). If so, data with a missing or invalid magic is probably a
+    /// damaged replay rather than some other kind of file.
+    fn has_magic_chunk(reader: &mut R) -> Result<bool, BroodrepError> {
+        reader.seek(SeekFrom::Start(4))?;
+        let mut chunk_header = [0; 8];
+        match reader.read_exact(&mut chunk_header) {
+            Ok(()) => Ok(chunk_header == [1, 0, 0, 0, 4, 0, 0, 0]),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -299,182 +1432,447 @@ impl<R: Read + Seek> Replay<R> {
         })
     }
 
+    /// Checks that `size` bytes can be read from the current position of `reader` without going past
+    /// the end of the data, so that crafted chunk sizes can't cause huge allocations or seeks.
+    fn check_remaining(reader: &mut R, stream_len: u64, size: u32) -> Result<(), BroodrepError> {
+        let position = reader.stream_position()?;
+        if position.saturating_add(size as u64) > stream_len {
+            return Err(BroodrepError::MalformedSection(
+                "size extends past the end of the file",
+            ));
+        }
+        Ok(())
+    }
+
     fn read_legacy_section(
         reader: &mut R,
+        stream_len: u64,
         format: ReplayFormat,
         config: DecompressionConfig,
         size_hint: Option<usize>,
+        decompressed_total: &mut u64,
+        scratch: &mut Vec<u8>,
     ) -> Result<Vec<u8>, BroodrepError> {
         let header = Self::read_section_header(reader)?;
         let mut data = Vec::with_capacity(size_hint.unwrap_or(0));
         for _ in 0..header.num_chunks {
             let size = reader.read_u32::<LE>()?;
+            Self::check_remaining(reader, stream_len, size)?;
             data.reserve(size as usize);
-            // TODO(tec27): Keep a working buffer around to avoid needing to reallocate buffers
-            // frequently? Peek the first byte and seek back to avoid needing this allocation at
-            // all?
-            let mut compressed = vec![0; size as usize];
-            reader.read_exact(&mut compressed)?;
+            // Limit each chunk to whatever is left of the total budget as well
+            let remaining = config
+                .max_total_decompressed_size
+                .map(|max| max.saturating_sub(*decompressed_total + data.len() as u64));
+            let config = DecompressionConfig {
+                max_decompressed_size: remaining.map_or(config.max_decompressed_size, |r| {
+                    r.min(config.max_decompressed_size)
+                }),
+                ..config
+            };
+            let size = size as usize;
 
             match format {
                 ReplayFormat::Legacy => {
+                    scratch.clear();
+                    scratch.resize(size, 0);
+                    reader.read_exact(scratch)?;
                     let mut decoder = SafeDecompressor::new(
-                        ExplodeReader::new(&compressed[..]),
+                        ExplodeReader::new(&scratch[..]),
                         config,
                         Some(size as u64),
                     );
                     decoder.read_to_end(&mut data)?;
                 }
                 ReplayFormat::Modern | ReplayFormat::Modern121 => {
-                    if size <= 4 || compressed[0] != 0x78 {
+                    // Peek at the start of the chunk so that uncompressed data (the common case for
+                    // most sections) can be read straight into the output
+                    let mut prefix = [0u8; 2];
+                    let prefix = &mut prefix[..size.min(2)];
+                    reader.read_exact(prefix)?;
+
+                    if !is_zlib_header(prefix, size) {
                         // Not compressed, we can return it directly
-                        data.extend(compressed);
+                        if size as u64 > config.max_decompressed_size {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                DecompressionError::SizeLimitExceeded,
+                            )
+                            .into());
+                        }
+                        data.extend_from_slice(prefix);
+                        let start = data.len();
+                        data.resize(start + size - prefix.len(), 0);
+                        reader.read_exact(&mut data[start..])?;
                     } else {
+                        scratch.clear();
+                        scratch.extend_from_slice(prefix);
+                        scratch.resize(size, 0);
+                        reader.read_exact(&mut scratch[prefix.len()..])?;
+                        let compressed = &scratch[..];
+
                         let mut decoder = SafeDecompressor::new(
-                            ZlibDecoder::new(&compressed[..]),
+                            ZlibDecoder::new(compressed),
                             config,
                             Some(size as u64),
                         );
-                        decoder.read_to_end(&mut data)?;
+                        let start = data.len();
+                        match decoder.read_to_end(&mut data) {
+                            Ok(_) => {}
+                            // Limits being exceeded means this was (probably malicious) zlib data
+                            Err(e) if e.get_ref().is_some_and(|e| e.is::<DecompressionError>()) => {
+                                return Err(e.into());
+                            }
+                            // Otherwise this was uncompressed data that just happened to look like
+                            // a zlib header
+                            Err(_) => {
+                                data.truncate(start);
+                                if size as u64 > config.max_decompressed_size {
+                                    return Err(std::io::Error::new(
+                                        std::io::ErrorKind::InvalidData,
+                                        DecompressionError::SizeLimitExceeded,
+                                    )
+                                    .into());
+                                }
+                                data.extend_from_slice(compressed);
+                            }
+                        }
                     }
                 }
             }
         }
 
+        // Don't let one unusually large chunk keep a large allocation around for the life of the
+        // Replay
+        if scratch.capacity() > MAX_SCRATCH_CAPACITY {
+            *scratch = Vec::new();
+        }
+
+        *decompressed_total += data.len() as u64;
         Ok(data)
     }
 
     /// Reads the header and then skips over a section without parsing it.
-    fn skip_legacy_section(reader: &mut R) -> Result<(), BroodrepError> {
+    fn skip_legacy_section(reader: &mut R, stream_len: u64) -> Result<(), BroodrepError> {
+        Self::read_chunk_layout(reader, stream_len)?;
+        Ok(())
+    }
+
+    /// Reads the header of a legacy section and walks over its chunks, returning their locations.
+    /// The reader will be positioned at the end of the section afterwards.
+    fn read_chunk_layout(reader: &mut R, stream_len: u64) -> Result<Vec<ChunkInfo>, BroodrepError> {
         let header = Self::read_section_header(reader)?;
+        let mut chunks = Vec::new();
         for _ in 0..header.num_chunks {
             let size = reader.read_u32::<LE>()?;
+            Self::check_remaining(reader, stream_len, size)?;
+            chunks.push(ChunkInfo {
+                offset: reader.stream_position()?,
+                compressed_size: size,
+            });
             reader.seek(SeekFrom::Current(size as i64))?;
         }
-        Ok(())
+        Ok(chunks)
     }
+}
 
-    fn parse_replay_header(data: &[u8]) -> Result<ReplayHeader, BroodrepError> {
-        let mut cursor = Cursor::new(data);
-        let engine = cursor.read_u8()?.into();
-        let frames = cursor.read_u32::<LE>()?;
-
-        cursor.seek(SeekFrom::Current(3))?; // replay_campaign_mission + 0x48 (lobby init command)
-
-        let start_time = cursor.read_u32::<LE>()?;
-
-        cursor.seek(SeekFrom::Current(12))?; // player bytes
-
-        // TODO(tec27): Handle non-UTF-8 string formats
-        let mut title = vec![0u8; 29];
-        cursor.read_exact(&mut title[..28])?;
-        let title = CStr::from_bytes_until_nul(&title)
-            // This should never happen (we left an extra byte to ensure the null) but just in case
-            .map_err(|_e| BroodrepError::MalformedHeader("invalid title"))?
-            .to_string_lossy()
-            .into_owned();
-
-        let map_width = cursor.read_u16::<LE>()?;
-        let map_height = cursor.read_u16::<LE>()?;
-        cursor.seek(SeekFrom::Current(1))?; // unused/padding?
-        let available_slots = cursor.read_u8()?;
-        let speed = cursor.read_u8()?.try_into()?;
-        cursor.seek(SeekFrom::Current(1))?; // unused/padding?
-        let game_type = cursor.read_u16::<LE>()?.into();
-        let game_sub_type = cursor.read_u16::<LE>()?;
-
-        cursor.seek(SeekFrom::Current(8))?; // unknown
-
-        let mut host_name = vec![0u8; 25];
-        cursor.read_exact(&mut host_name[..24])?;
-        let host_name = CStr::from_bytes_until_nul(&host_name)
-            // This should never happen (we left an extra byte to ensure the null) but just in case
-            .map_err(|_e| BroodrepError::MalformedHeader("invalid host name"))?
-            .to_string_lossy()
-            .into_owned();
-
-        cursor.seek(SeekFrom::Current(1))?; // unknown
-
-        let mut map_name = vec![0u8; 27];
-        cursor.read_exact(&mut map_name[..26])?;
-        let map_name = CStr::from_bytes_until_nul(&map_name)
-            // This should never happen (we left an extra byte to ensure the null) but just in case
-            .map_err(|_e| BroodrepError::MalformedHeader("invalid map name"))?
-            .to_string_lossy()
-            .into_owned();
-
-        cursor.seek(SeekFrom::Current(38))?; // unknown
-
-        let players = (0..12)
-            .map(|_i| {
-                let slot_id = cursor.read_u16::<LE>()?;
-                cursor.seek(SeekFrom::Current(2))?; // unknown
-                let network_id = cursor.read_u8()?;
-                cursor.seek(SeekFrom::Current(3))?; // unknown
-                let player_type: PlayerType = cursor.read_u8()?.try_into()?;
-                let race: Race = cursor.read_u8()?.into();
-                let team = cursor.read_u8()?;
-                let mut name = vec![0u8; 26];
-                cursor.read_exact(&mut name[..25])?;
-                let name = CStr::from_bytes_until_nul(&name)
-                    // This should never happen (we left an extra byte to ensure the null) but just
-                    // in case
-                    .map_err(|_e| BroodrepError::MalformedHeader("invalid player name"))?
-                    .to_string_lossy()
-                    .into_owned();
-
-                Ok::<Player, BroodrepError>(Player {
-                    slot_id,
-                    network_id,
-                    player_type,
-                    race,
-                    team,
-                    name,
-                })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(ReplayHeader {
-            engine,
-            frames,
-            start_time,
-            title,
-            map_width,
-            map_height,
-            available_slots,
-            speed,
-            game_type,
-            game_sub_type,
-            host_name,
-            map_name,
-            slots: players,
-        })
+/// How many steps of each player's build order [build_order_similarity] compares.
+const SIMILARITY_BUILD_ORDER_STEPS: usize = 20;
+
+/// Returns how similar the openings played in two replays were, from 0.0 (nothing in common) to
+/// 1.0 (identical), or [None] if the replays have no races in common to compare.
+///
+/// Players are matched up by race (in slot order, if a race was played by more than one player in
+/// a game), and the first 20 steps of each matched pair's build orders (every unit built,
+/// trained, or morphed into) are compared. Each pair's score is 1 minus the edit distance between
+/// their build orders, normalized by the length of the longer one, so build orders of different
+/// lengths are penalized for the steps one of them is missing. The result is the mean of the
+/// scores of all matched pairs; players whose race has no counterpart in the other replay are
+/// ignored.
+pub fn build_order_similarity<A: Read + Seek, B: Read + Seek>(
+    a: &mut Replay<A>,
+    b: &mut Replay<B>,
+) -> Result<Option<f64>, BroodrepError> {
+    let a_commands = a.commands()?;
+    let b_commands = b.commands()?;
+
+    let mut b_players = b.players().collect::<Vec<_>>();
+    let mut scores = Vec::new();
+    for a_player in a.players() {
+        let Some(index) = b_players.iter().position(|p| p.race == a_player.race) else {
+            continue;
+        };
+        let b_player = b_players.remove(index);
+        scores.push(analysis::build_order_similarity(
+            &analysis::build_order(
+                &a_commands,
+                a_player.network_id,
+                SIMILARITY_BUILD_ORDER_STEPS,
+            ),
+            &analysis::build_order(
+                &b_commands,
+                b_player.network_id,
+                SIMILARITY_BUILD_ORDER_STEPS,
+            ),
+        ));
     }
-}
 
-/// The format version of a replay.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum ReplayFormat {
-    /// The replay was created with a version before 1.18.
-    Legacy,
-    /// The replay was created with a version between 1.18 and 1.21.
-    Modern,
-    /// The replay was created with version 1.21 or later.
-    Modern121,
+    if scores.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(scores.iter().sum::<f64>() / scores.len() as f64))
+    }
 }
 
-impl fmt::Display for ReplayFormat {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ReplayFormat::Legacy => write!(f, "Legacy (pre-1.18)"),
-            ReplayFormat::Modern => write!(f, "Modern (1.18-1.21)"),
-            ReplayFormat::Modern121 => write!(f, "Modern (1.21+)"),
+/// Turns an error caused by running out of data while reading part of the replay header (e.g.
+/// because the file was only partially uploaded) into a [BroodrepError::MalformedHeader] with
+/// `message`. Other errors are returned unchanged.
+fn truncated_header(err: BroodrepError, message: &'static str) -> BroodrepError {
+    match err {
+        BroodrepError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            BroodrepError::MalformedHeader(message)
         }
+        err => err,
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum ReplaySection {
+/// Determines the [ReplayFormat] of a replay from the first [FORMAT_DETECTION_LEN] bytes of the
+/// file, without needing the rest of it. This allows data that is arriving incrementally (e.g. over
+/// the network) to be rejected early if it isn't a replay. Returns the same errors that
+/// [Replay::new] would for a file starting with `prefix`.
+pub fn detect_replay_format(prefix: &[u8]) -> Result<ReplayFormat, BroodrepError> {
+    Replay::<Cursor<&[u8]>>::detect_format(&mut Cursor::new(prefix))
+}
+
+/// Parses the contents of a replay's (decompressed) [Header](ReplaySection::Header) section. This
+/// is what [Replay] uses internally, and is useful if the header bytes have already been extracted
+/// some other way (e.g. with [Replay::get_raw_section]).
+///
+/// `data` should contain the entire section, which is 0x279 bytes in all replay formats. Only the
+/// first 0x251 bytes (up to the end of the last player slot) are actually read, so anything shorter
+/// than that will result in an error.
+pub fn parse_replay_header(data: &[u8]) -> Result<ReplayHeader, BroodrepError> {
+    parse_replay_header_with_encodings(data, StringEncoding::Auto, &[])
+}
+
+/// Like [parse_replay_header], but strings will be decoded using `string_encoding` and
+/// `fallback_encodings` (see [ParseConfig::string_encoding] and [ParseConfig::fallback_encodings]
+/// for how they are used).
+pub fn parse_replay_header_with_encodings(
+    data: &[u8],
+    string_encoding: StringEncoding,
+    fallback_encodings: &[TextEncoding],
+) -> Result<ReplayHeader, BroodrepError> {
+    if data.len() < MIN_SIZE_HEADER {
+        return Err(BroodrepError::MalformedHeader("header is too short"));
+    }
+
+    let mut cursor = Cursor::new(data);
+    let engine = cursor.read_u8()?.into();
+    let frames = cursor.read_u32::<LE>()?;
+
+    cursor.seek(SeekFrom::Current(3))?; // replay_campaign_mission + 0x48 (lobby init command)
+
+    let start_time = cursor.read_u32::<LE>()?;
+
+    cursor.seek(SeekFrom::Current(12))?; // player bytes
+
+    let mut title = vec![0u8; 29];
+    cursor.read_exact(&mut title[..28])?;
+    let raw_title = CStr::from_bytes_until_nul(&title)
+        // This should never happen (we left an extra byte to ensure the null) but just in case
+        .map_err(|_e| BroodrepError::MalformedHeader("invalid title"))?
+        .to_bytes()
+        .to_vec();
+    let title = encoding::decode_string(&raw_title, string_encoding, fallback_encodings);
+
+    let map_width = cursor.read_u16::<LE>()?;
+    let map_height = cursor.read_u16::<LE>()?;
+    cursor.seek(SeekFrom::Current(1))?; // unused/padding?
+    let available_slots = cursor.read_u8()?;
+    let speed = cursor.read_u8()?.into();
+    cursor.seek(SeekFrom::Current(1))?; // unused/padding?
+    let game_type = cursor.read_u16::<LE>()?.into();
+    let game_sub_type = cursor.read_u16::<LE>()?;
+
+    cursor.seek(SeekFrom::Current(8))?; // unknown
+
+    let mut host_name = vec![0u8; 25];
+    cursor.read_exact(&mut host_name[..24])?;
+    let raw_host_name = CStr::from_bytes_until_nul(&host_name)
+        // This should never happen (we left an extra byte to ensure the null) but just in case
+        .map_err(|_e| BroodrepError::MalformedHeader("invalid host name"))?
+        .to_bytes()
+        .to_vec();
+    let host_name = encoding::decode_string(&raw_host_name, string_encoding, fallback_encodings);
+
+    cursor.seek(SeekFrom::Current(1))?; // unknown
+
+    let mut map_name = vec![0u8; 27];
+    cursor.read_exact(&mut map_name[..26])?;
+    let raw_map_name = CStr::from_bytes_until_nul(&map_name)
+        // This should never happen (we left an extra byte to ensure the null) but just in case
+        .map_err(|_e| BroodrepError::MalformedHeader("invalid map name"))?
+        .to_bytes()
+        .to_vec();
+    let map_name = encoding::decode_string(&raw_map_name, string_encoding, fallback_encodings);
+
+    // NOTE: Lobby rules (shared vision/control, random start locations, etc.) aren't stored in any
+    // region of the header we've been able to identify, and the GCFG section's layout is still
+    // unknown. If they turn up in either, they should be exposed as a separate struct rather than
+    // guessed at here.
+    cursor.seek(SeekFrom::Current(38))?; // unknown
+
+    debug_assert_eq!(cursor.position() as usize, HEADER_SLOTS_OFFSET);
+    let players = (0..NUM_HEADER_SLOTS)
+        .map(|_i| {
+            let slot_id = cursor.read_u16::<LE>()?;
+            cursor.seek(SeekFrom::Current(2))?; // unknown
+            let network_id = cursor.read_u8()?;
+            cursor.seek(SeekFrom::Current(3))?; // unknown
+            let player_type: PlayerType = cursor.read_u8()?.into();
+            let race: Race = cursor.read_u8()?.into();
+            let team = cursor.read_u8()?;
+            let mut name = vec![0u8; 26];
+            cursor.read_exact(&mut name[..25])?;
+            let raw_name = CStr::from_bytes_until_nul(&name)
+                // This should never happen (we left an extra byte to ensure the null) but just
+                // in case
+                .map_err(|_e| BroodrepError::MalformedHeader("invalid player name"))?
+                .to_bytes()
+                .to_vec();
+            let name = encoding::decode_string(&raw_name, string_encoding, fallback_encodings);
+
+            Ok::<Player, BroodrepError>(Player {
+                slot_id,
+                network_id,
+                player_type,
+                race,
+                team,
+                name,
+                raw_name,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ReplayHeader {
+        engine,
+        frames,
+        start_time,
+        title,
+        raw_title,
+        map_width,
+        map_height,
+        available_slots,
+        speed,
+        game_type,
+        game_sub_type,
+        host_name,
+        raw_host_name,
+        map_name,
+        raw_map_name,
+        slots: players,
+    })
+}
+
+/// The parsed metadata of a [Replay], without the underlying reader. See [Replay::summary] and
+/// [Replay::from_parts].
+#[derive(Debug, Clone)]
+pub struct ReplaySummary {
+    pub format: ReplayFormat,
+    pub header: ReplayHeader,
+    decompression_config: DecompressionConfig,
+    command_parse_config: CommandParseConfig,
+    string_encoding: StringEncoding,
+    fallback_encodings: Vec<TextEncoding>,
+    stream_len: u64,
+    section_offsets: HashMap<ReplaySection, u64>,
+    incomplete_section: Option<ReplaySection>,
+    trailing_data: Option<Range<u64>>,
+}
+
+/// Returns whether a chunk of `len` bytes starting with `prefix` has a valid zlib header (as
+/// opposed to being uncompressed data). Modern replays store chunks uncompressed when compression
+/// wouldn't reduce their size, so this is necessary to tell the two apart.
+fn is_zlib_header(prefix: &[u8], len: usize) -> bool {
+    if len <= 4 || prefix.len() < 2 {
+        return false;
+    }
+    let (cmf, flg) = (prefix[0], prefix[1]);
+    // Deflate with a 32K window (what SC:R always uses), a valid header checksum, and no preset
+    // dictionary
+    cmf == 0x78 && u16::from_be_bytes([cmf, flg]) % 31 == 0 && flg & 0x20 == 0
+}
+
+/// The results of checking a replay's section checksums. See [Replay::verify_checksums].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumReport {
+    /// The checksum results for each section that was present in the replay, in file order.
+    pub sections: Vec<SectionChecksum>,
+}
+
+impl ChecksumReport {
+    /// Returns true if every section that was checked matched its checksum.
+    pub fn is_valid(&self) -> bool {
+        self.sections.iter().all(|s| s.is_valid())
+    }
+
+    /// Returns the sections whose data didn't match their checksum.
+    pub fn mismatches(&self) -> impl Iterator<Item = &SectionChecksum> {
+        self.sections.iter().filter(|s| !s.is_valid())
+    }
+}
+
+/// The checksum result for a single section. See [Replay::verify_checksums].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SectionChecksum {
+    pub section: ReplaySection,
+    /// The checksum stored in the section's header, or [None] if the file ended before it.
+    pub expected: Option<u32>,
+    /// The checksum of the section's data, or [None] if the file ended before all of its data.
+    pub actual: Option<u32>,
+}
+
+impl SectionChecksum {
+    /// Returns true if the section's data could be read and matched its stored checksum.
+    pub fn is_valid(&self) -> bool {
+        self.expected.is_some() && self.expected == self.actual
+    }
+}
+
+/// The location of a single chunk of section data within a replay file. See
+/// [Replay::section_chunk_layout].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkInfo {
+    /// The offset from the beginning of the file to the start of the chunk's data.
+    pub offset: u64,
+    /// The size of the chunk's data as stored in the file (i.e. before decompression).
+    pub compressed_size: u32,
+}
+
+/// The format version of a replay.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayFormat {
+    /// The replay was created with a version before 1.18.
+    Legacy,
+    /// The replay was created with a version between 1.18 and 1.21.
+    Modern,
+    /// The replay was created with version 1.21 or later.
+    Modern121,
+}
+
+impl fmt::Display for ReplayFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayFormat::Legacy => write!(f, "Legacy (pre-1.18)"),
+            ReplayFormat::Modern => write!(f, "Modern (1.18-1.21)"),
+            ReplayFormat::Modern121 => write!(f, "Modern (1.21+)"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReplaySection {
     /// Header containing basic game information and player slots
     Header,
     /// Commands issued by players during the game
@@ -484,21 +1882,31 @@ pub enum ReplaySection {
     /// Longer strings for player names (that also seem to always be utf-8, so safer to decode)
     PlayerNames,
     /// Building/unit skin settings for players
+    ///
+    /// NOTE: The layout of this section (0x15e0 bytes) hasn't been worked out yet, so it isn't
+    /// parsed. Once it is, the skin IDs should be exposed similar to
+    /// [Replay::get_shieldbattery_section]; until then the raw data is available through
+    /// [Replay::get_raw_section].
     Skins,
     /// Unit/sprite limits for the game
     Limits,
-    /// Bug fix(es)? TODO(tec27): Figure out what this actually is :)
+    /// Flags for the game bug fixes that were enabled (see [Replay::bugfixes])
     Bfix,
     /// Custom (extended) team color settings
     CustomColors,
     /// Game configuration? TODO(tec27): Figure out what this actually is :)
+    ///
+    /// NOTE: This is the only place that settings like observer vision could plausibly be stored
+    /// (neither the header nor the ShieldBattery section contain them), but until its layout is
+    /// known broodrep can't expose them. It is still available through
+    /// [Replay::get_raw_section].
     Gcfg,
 
     // Non-official sections
     ShieldBattery,
 
     /// Any section that is not one of the "official" types, or directly supported by broodrep
-    Custom([u8; 4]),
+    Custom(SectionTag),
 }
 
 impl ReplaySection {
@@ -517,6 +1925,24 @@ impl ReplaySection {
         )
     }
 
+    /// Returns the ID that precedes this section in the replay file, or [None] if it isn't a
+    /// [modern](ReplaySection::is_modern) section.
+    pub fn tag(&self) -> Option<SectionTag> {
+        match self {
+            ReplaySection::Header
+            | ReplaySection::Commands
+            | ReplaySection::MapData
+            | ReplaySection::PlayerNames => None,
+            ReplaySection::Skins => Some(SectionTag(*b"SKIN")),
+            ReplaySection::Limits => Some(SectionTag(*b"LMTS")),
+            ReplaySection::Bfix => Some(SectionTag(*b"BFIX")),
+            ReplaySection::CustomColors => Some(SectionTag(*b"CCLR")),
+            ReplaySection::Gcfg => Some(SectionTag(*b"GCFG")),
+            ReplaySection::ShieldBattery => Some(SectionTag(*b"Sbat")),
+            ReplaySection::Custom(tag) => Some(*tag),
+        }
+    }
+
     pub fn size_hint(&self) -> Option<usize> {
         match self {
             ReplaySection::Header => Some(SIZE_HEADER),
@@ -540,7 +1966,7 @@ impl From<&[u8; 4]> for ReplaySection {
             b"CCLR" => ReplaySection::CustomColors,
             b"GCFG" => ReplaySection::Gcfg,
             b"Sbat" => ReplaySection::ShieldBattery,
-            id => ReplaySection::Custom(*id),
+            id => ReplaySection::Custom(SectionTag(*id)),
         }
     }
 }
@@ -551,15 +1977,82 @@ impl From<[u8; 4]> for ReplaySection {
     }
 }
 
+impl From<SectionTag> for ReplaySection {
+    fn from(value: SectionTag) -> Self {
+        (&value.0).into()
+    }
+}
+
+/// The 4-byte ID that precedes a modern section in a replay file (e.g. `SKIN`). These are usually,
+/// but not necessarily, printable ASCII.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SectionTag(pub [u8; 4]);
+
+impl fmt::Display for SectionTag {
+    /// Writes the tag as ASCII, escaping any bytes that aren't printable (e.g. `\x00`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &b in &self.0 {
+            write!(f, "{}", std::ascii::escape_default(b))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SectionTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SectionTag(\"{self}\")")
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("section tags must be exactly 4 bytes long")]
+pub struct InvalidSectionTag;
+
+impl std::str::FromStr for SectionTag {
+    type Err = InvalidSectionTag;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SectionTag(
+            s.as_bytes().try_into().map_err(|_| InvalidSectionTag)?,
+        ))
+    }
+}
+
+impl From<[u8; 4]> for SectionTag {
+    fn from(value: [u8; 4]) -> Self {
+        SectionTag(value)
+    }
+}
+
+impl From<SectionTag> for [u8; 4] {
+    fn from(value: SectionTag) -> Self {
+        value.0
+    }
+}
+
+/// Converts from a section ID stored as a little-endian `u32` (i.e. the first character of the tag
+/// is the least significant byte).
+impl From<u32> for SectionTag {
+    fn from(value: u32) -> Self {
+        SectionTag(value.to_le_bytes())
+    }
+}
+
+impl From<SectionTag> for u32 {
+    fn from(value: SectionTag) -> Self {
+        u32::from_le_bytes(value.0)
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 struct SectionHeader {
-    #[expect(dead_code)]
     checksum: u32,
     num_chunks: u32,
 }
 
 /// The engine the game was played under.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Engine {
     StarCraft,
     BroodWar,
@@ -587,36 +2080,37 @@ impl fmt::Display for Engine {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GameSpeed {
-    Slowest = 0,
-    Slower = 1,
-    Slow = 2,
-    Normal = 3,
-    Fast = 4,
-    Faster = 5,
-    Fastest = 6,
+    Slowest,
+    Slower,
+    Slow,
+    Normal,
+    Fast,
+    Faster,
+    Fastest,
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for GameSpeed {
-    type Error = BroodrepError;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<u8> for GameSpeed {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(GameSpeed::Slowest),
-            1 => Ok(GameSpeed::Slower),
-            2 => Ok(GameSpeed::Slow),
-            3 => Ok(GameSpeed::Normal),
-            4 => Ok(GameSpeed::Fast),
-            5 => Ok(GameSpeed::Faster),
-            6 => Ok(GameSpeed::Fastest),
-            _ => Err(BroodrepError::MalformedHeader("invalid game speed")),
+            0 => GameSpeed::Slowest,
+            1 => GameSpeed::Slower,
+            2 => GameSpeed::Slow,
+            3 => GameSpeed::Normal,
+            4 => GameSpeed::Fast,
+            5 => GameSpeed::Faster,
+            6 => GameSpeed::Fastest,
+            other => GameSpeed::Unknown(other),
         }
     }
 }
 
 impl GameSpeed {
     /// Returns the duration per logical step for this game speed.
-    /// These timing values are based on StarCraft's actual frame timings.
+    /// These timing values are based on StarCraft's actual frame timings. Unknown speeds use the
+    /// timing for [GameSpeed::Fastest], as that's what the vast majority of games are played on.
     pub fn time_per_step(self) -> std::time::Duration {
         let millis = match self {
             GameSpeed::Slowest => 167,
@@ -625,7 +2119,7 @@ impl GameSpeed {
             GameSpeed::Normal => 67,
             GameSpeed::Fast => 56,
             GameSpeed::Faster => 48,
-            GameSpeed::Fastest => 42,
+            GameSpeed::Fastest | GameSpeed::Unknown(_) => 42,
         };
         std::time::Duration::from_millis(millis)
     }
@@ -641,11 +2135,75 @@ impl fmt::Display for GameSpeed {
             GameSpeed::Fast => write!(f, "Fast"),
             GameSpeed::Faster => write!(f, "Faster"),
             GameSpeed::Fastest => write!(f, "Fastest"),
+            GameSpeed::Unknown(value) => write!(f, "Unknown ({})", value),
+        }
+    }
+}
+
+/// An RGB color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl PlayerColor {
+    const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// The color each player slot has by default in Brood War, indexed by slot ID: red, blue, teal,
+/// purple, orange, brown, white, yellow, green, pale yellow, tan, and azure.
+pub const STANDARD_PLAYER_COLORS: [PlayerColor; 12] = [
+    PlayerColor::new(244, 4, 4),
+    PlayerColor::new(12, 72, 204),
+    PlayerColor::new(44, 180, 148),
+    PlayerColor::new(136, 64, 156),
+    PlayerColor::new(248, 140, 20),
+    PlayerColor::new(112, 48, 20),
+    PlayerColor::new(204, 224, 208),
+    PlayerColor::new(252, 252, 56),
+    PlayerColor::new(8, 128, 8),
+    PlayerColor::new(252, 252, 124),
+    PlayerColor::new(236, 196, 176),
+    PlayerColor::new(64, 104, 212),
+];
+
+/// The network latency setting (how many frames commands are delayed by before being executed).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TurnRate {
+    Low,
+    High,
+    ExtraHigh,
+    Unknown(u8),
+}
+
+impl From<u8> for TurnRate {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => TurnRate::Low,
+            1 => TurnRate::High,
+            2 => TurnRate::ExtraHigh,
+            other => TurnRate::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for TurnRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnRate::Low => write!(f, "Low Latency"),
+            TurnRate::High => write!(f, "High Latency"),
+            TurnRate::ExtraHigh => write!(f, "Extra High Latency"),
+            TurnRate::Unknown(value) => write!(f, "Unknown ({})", value),
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GameType {
     None,
     Melee,
@@ -711,20 +2269,55 @@ impl fmt::Display for GameType {
     }
 }
 
+/// How the players in a game were split into teams, as determined by its [GameType] and
+/// [game_sub_type](ReplayHeader::game_sub_type).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TeamLayout {
+    /// The game type doesn't have fixed teams (although players may have allied each other
+    /// ingame).
+    None,
+    /// A Top vs Bottom game.
+    TopVsBottom {
+        /// The number of slots on the top team.
+        top_count: u8,
+        /// The slot IDs of the players on each team, ordered by team.
+        teams: Vec<Vec<u16>>,
+    },
+    /// A Team Melee, Team Free For All, or Team Capture The Flag game.
+    TeamGame {
+        /// The number of slots on each team (the last team may have fewer).
+        slots_per_team: u8,
+        /// The slot IDs of the players on each team, ordered by team. Teams with no players are
+        /// omitted.
+        teams: Vec<Vec<u16>>,
+    },
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReplayHeader {
     pub engine: Engine,
     /// How many game frames this replays contains actions for.
+    ///
+    /// NOTE: For games that were loaded from a save, we haven't found anything in the header (or
+    /// any other section) recording the frame the save was made on, so this and the frames of
+    /// commands can't be adjusted to account for the time played before the save.
     pub frames: u32,
     /// The time the game started at. This is actually the game's random seed, but since the game
     /// always uses the current unix timestamp as a seed, it also represents the local time the game
     /// began (local to the host).
     pub start_time: u32,
     pub title: String,
+    /// The bytes [title](ReplayHeader::title) was decoded from (without the null terminator), for
+    /// callers that want to handle its encoding themselves.
+    pub raw_title: Vec<u8>,
     /// Map width in tiles
     pub map_width: u16,
     /// Map height in tiles
     pub map_height: u16,
+    /// The number of slots the game was created with. This says nothing about how many of them
+    /// were filled, see [Replay::occupied_slot_count] for that.
     pub available_slots: u8,
     pub speed: GameSpeed,
     pub game_type: GameType,
@@ -732,12 +2325,103 @@ pub struct ReplayHeader {
     /// specifies the number of slots on each team (truncating the last team if necessary).
     pub game_sub_type: u16,
     pub host_name: String,
+    /// The bytes [host_name](ReplayHeader::host_name) was decoded from (without the null
+    /// terminator).
+    pub raw_host_name: Vec<u8>,
     pub map_name: String,
+    /// The bytes [map_name](ReplayHeader::map_name) was decoded from (without the null
+    /// terminator).
+    pub raw_map_name: Vec<u8>,
     /// All of the slots in the game, including empty slots.
     pub slots: Vec<Player>,
 }
 
 impl ReplayHeader {
+    /// Returns the range of bytes within the (decompressed) header section that contains the data
+    /// for the player slot at `index`. This can be used to modify a single slot without needing to
+    /// rewrite the entire header.
+    ///
+    /// Each slot is 36 bytes, laid out as follows (offsets are relative to the start of the range):
+    ///
+    /// | Offset | Size | Field                                             |
+    /// |--------|------|---------------------------------------------------|
+    /// | 0x00   | 2    | [Player::slot_id]                                 |
+    /// | 0x02   | 2    | Unknown                                           |
+    /// | 0x04   | 1    | [Player::network_id]                              |
+    /// | 0x05   | 3    | Unknown                                           |
+    /// | 0x08   | 1    | [Player::player_type]                             |
+    /// | 0x09   | 1    | [Player::race]                                    |
+    /// | 0x0A   | 1    | [Player::team]                                    |
+    /// | 0x0B   | 25   | [Player::name], null-terminated (24 bytes + null) |
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not a valid slot index (the header contains 12 slots).
+    pub fn slot_byte_range(index: usize) -> Range<usize> {
+        assert!(
+            index < NUM_HEADER_SLOTS,
+            "slot index {index} out of range (header contains {NUM_HEADER_SLOTS} slots)"
+        );
+        let start = HEADER_SLOTS_OFFSET + index * SIZE_HEADER_SLOT;
+        start..start + SIZE_HEADER_SLOT
+    }
+
+    /// Returns an error if any of the values in the header weren't recognized (and thus were
+    /// parsed into an `Unknown` variant).
+    fn check_known_values(&self) -> Result<(), BroodrepError> {
+        if matches!(self.speed, GameSpeed::Unknown(_)) {
+            return Err(BroodrepError::MalformedHeader("invalid game speed"));
+        }
+        if self
+            .slots
+            .iter()
+            .any(|p| matches!(p.player_type, PlayerType::Unknown(_)))
+        {
+            return Err(BroodrepError::MalformedHeader("invalid player type"));
+        }
+        Ok(())
+    }
+
+    /// Returns the player (or observer) that issued commands with the specified
+    /// [player_id](Command::player_id), or [None] if no slot matches it.
+    ///
+    /// The ID used in the command stream is the player's [network_id](Player::network_id), which is
+    /// unrelated to the [slot_id](Player::slot_id) of the slot they occupied.
+    pub fn player_for_command(&self, command_player_id: u8) -> Option<&Player> {
+        // Computer players all share the same network ID, and don't issue commands
+        if command_player_id == 255 {
+            return None;
+        }
+        self.slots
+            .iter()
+            .find(|p| !p.is_empty() && p.network_id == command_player_id)
+    }
+
+    /// Returns how the players in the game were split into teams. Observers are not included.
+    pub fn team_layout(&self) -> TeamLayout {
+        let teams = || {
+            let mut teams = BTreeMap::<u8, Vec<u16>>::new();
+            for player in self.players() {
+                teams.entry(player.team).or_default().push(player.slot_id);
+            }
+            teams.into_values().collect()
+        };
+
+        match self.game_type {
+            GameType::TopVsBottom => TeamLayout::TopVsBottom {
+                top_count: self.game_sub_type as u8,
+                teams: teams(),
+            },
+            GameType::TeamMelee | GameType::TeamFreeForAll | GameType::TeamCaptureTheFlag => {
+                TeamLayout::TeamGame {
+                    slots_per_team: self.game_sub_type as u8,
+                    teams: teams(),
+                }
+            }
+            _ => TeamLayout::None,
+        }
+    }
+
     /// Returns an iterator over all of the filled slots in the game (not including observers).
     pub fn players(&self) -> impl Iterator<Item = &Player> {
         self.slots
@@ -751,9 +2435,38 @@ impl ReplayHeader {
             .iter()
             .filter(|p| !p.is_empty() && p.is_observer())
     }
+
+    /// Returns the IDs (in the command stream) of the players in a game between exactly 2
+    /// non-observer players on opposing sides, or [None] if this isn't such a game.
+    pub(crate) fn opposing_pair(&self) -> Option<[u8; 2]> {
+        match self.players().collect::<Vec<_>>()[..] {
+            [a, b] if !self.are_allied(a, b) => Some([a.network_id, b.network_id]),
+            _ => None,
+        }
+    }
+
+    /// Returns whether two (different) players were on the same team. Teams only exist in team
+    /// game types (e.g. Team Melee, Top vs Bottom), so in all other game types this will always
+    /// return false, as will any comparison involving an empty slot or an observer.
+    ///
+    /// Note that this reflects the teams set in the lobby. Alliances changed during the game, as
+    /// well as those set up by a UMS map, are not taken into account.
+    pub fn are_allied(&self, a: &Player, b: &Player) -> bool {
+        let is_team_game = matches!(
+            self.game_type,
+            GameType::TeamMelee
+                | GameType::TeamFreeForAll
+                | GameType::TeamCaptureTheFlag
+                | GameType::TopVsBottom
+        );
+        let is_player = |p: &Player| !p.is_empty() && !p.is_observer();
+
+        is_team_game && is_player(a) && is_player(b) && a.slot_id != b.slot_id && a.team == b.team
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Player {
     /// ID of the map slot the player was placed in (post-randomization, if applicable).
     pub slot_id: u16,
@@ -763,6 +2476,10 @@ pub struct Player {
     pub race: Race,
     pub team: u8,
     pub name: String,
+    /// The bytes [name](Player::name) was decoded from (without the null terminator). Since the
+    /// encoding used for names depends on the system of the player who recorded the replay, this
+    /// can be used to decode them in ways broodrep doesn't support.
+    pub raw_name: Vec<u8>,
     // TODO(tec27): implement colors
 }
 
@@ -778,34 +2495,62 @@ impl Player {
     }
 }
 
+/// A filled slot in a game, along with information about its occupant from outside the header.
+/// See [Replay::all_participants].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Participant {
+    pub player: Player,
+    /// The ID of the ShieldBattery account that occupied the slot, if the game was played on
+    /// ShieldBattery.
+    pub shieldbattery_user_id: Option<u32>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlayerType {
-    Inactive = 0,
-    Computer = 1,
-    Human = 2,
-    RescuePassive = 3,
-    Unused = 4,
-    ComputerControlled = 5,
-    Open = 6,
-    Neutral = 7,
-    Closed = 8,
+    Inactive,
+    Computer,
+    Human,
+    RescuePassive,
+    Unused,
+    ComputerControlled,
+    Open,
+    Neutral,
+    Closed,
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for PlayerType {
-    type Error = BroodrepError;
+impl From<u8> for PlayerType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => PlayerType::Inactive,
+            1 => PlayerType::Computer,
+            2 => PlayerType::Human,
+            3 => PlayerType::RescuePassive,
+            4 => PlayerType::Unused,
+            5 => PlayerType::ComputerControlled,
+            6 => PlayerType::Open,
+            7 => PlayerType::Neutral,
+            8 => PlayerType::Closed,
+            other => PlayerType::Unknown(other),
+        }
+    }
+}
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<PlayerType> for u8 {
+    fn from(value: PlayerType) -> Self {
         match value {
-            0 => Ok(PlayerType::Inactive),
-            1 => Ok(PlayerType::Computer),
-            2 => Ok(PlayerType::Human),
-            3 => Ok(PlayerType::RescuePassive),
-            4 => Ok(PlayerType::Unused),
-            5 => Ok(PlayerType::ComputerControlled),
-            6 => Ok(PlayerType::Open),
-            7 => Ok(PlayerType::Neutral),
-            8 => Ok(PlayerType::Closed),
-            _ => Err(BroodrepError::MalformedHeader("invalid player type")),
+            PlayerType::Inactive => 0,
+            PlayerType::Computer => 1,
+            PlayerType::Human => 2,
+            PlayerType::RescuePassive => 3,
+            PlayerType::Unused => 4,
+            PlayerType::ComputerControlled => 5,
+            PlayerType::Open => 6,
+            PlayerType::Neutral => 7,
+            PlayerType::Closed => 8,
+            PlayerType::Unknown(value) => value,
         }
     }
 }
@@ -822,18 +2567,21 @@ impl fmt::Display for PlayerType {
             PlayerType::Open => write!(f, "Open"),
             PlayerType::Neutral => write!(f, "Neutral"),
             PlayerType::Closed => write!(f, "Closed"),
+            PlayerType::Unknown(value) => write!(f, "Unknown ({})", value),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Race {
-    Zerg = 0,
-    Terran = 1,
-    Protoss = 2,
+    Zerg,
+    Terran,
+    Protoss,
     // NOTE(tec27): Generally this shouldn't be present for occupied slots in a replay (as it will
     // have been resolved by the replay write time), but for empty slots it may be
-    Random = 6,
+    Random,
+    Unknown(u8),
 }
 
 impl From<u8> for Race {
@@ -842,7 +2590,20 @@ impl From<u8> for Race {
             0 => Race::Zerg,
             1 => Race::Terran,
             2 => Race::Protoss,
-            _ => Race::Random,
+            6 => Race::Random,
+            other => Race::Unknown(other),
+        }
+    }
+}
+
+impl From<Race> for u8 {
+    fn from(value: Race) -> Self {
+        match value {
+            Race::Zerg => 0,
+            Race::Terran => 1,
+            Race::Protoss => 2,
+            Race::Random => 6,
+            Race::Unknown(value) => value,
         }
     }
 }
@@ -854,6 +2615,7 @@ impl fmt::Display for Race {
             Race::Terran => write!(f, "Terran"),
             Race::Protoss => write!(f, "Protoss"),
             Race::Random => write!(f, "Random"),
+            Race::Unknown(value) => write!(f, "Unknown ({})", value),
         }
     }
 }
@@ -871,41 +2633,1511 @@ mod tests {
     const SCR_121: &[u8] = include_bytes!("../testdata/scr_replay.rep");
     const SB_DATA: &[u8] = include_bytes!("../testdata/sb_data.rep");
 
+    /// Builds a minimal 1.21+ replay in memory, for testing things the replay fixtures don't cover.
+    #[derive(Clone)]
+    struct TestReplay {
+        header: Vec<u8>,
+        commands: Vec<u8>,
+        map_data: Vec<u8>,
+        player_names: Vec<u8>,
+        modern_sections: Vec<([u8; 4], Vec<u8>)>,
+    }
+
+    impl TestReplay {
+        fn new() -> Self {
+            let mut header = vec![0u8; SIZE_HEADER];
+            header[0] = 1; // Brood War
+            header[1..5].copy_from_slice(&1000u32.to_le_bytes());
+            header[8..12].copy_from_slice(&1578881288u32.to_le_bytes());
+            header[24..28].copy_from_slice(b"test");
+            header[52..54].copy_from_slice(&128u16.to_le_bytes());
+            header[54..56].copy_from_slice(&96u16.to_le_bytes());
+            header[57] = 2;
+            header[58] = 6; // Fastest
+            header[60..62].copy_from_slice(&2u16.to_le_bytes()); // Melee
+            header[62..64].copy_from_slice(&1u16.to_le_bytes());
+            header[97..105].copy_from_slice(b"Test Map");
+            for i in 0..12 {
+                let slot = 161 + i * 36;
+                header[slot..slot + 2].copy_from_slice(&(i as u16).to_le_bytes());
+                header[slot + 9] = Race::Random.into();
+            }
+
+            Self {
+                header,
+                commands: Vec::new(),
+                map_data: Vec::new(),
+                player_names: vec![0; SIZE_PLAYER_NAMES],
+                modern_sections: Vec::new(),
+            }
+        }
+
+        fn player(
+            mut self,
+            index: usize,
+            network_id: u8,
+            player_type: PlayerType,
+            race: Race,
+            team: u8,
+            name: &str,
+        ) -> Self {
+            let slot = 161 + index * 36;
+            self.header[slot + 4] = network_id;
+            self.header[slot + 8] = player_type.into();
+            self.header[slot + 9] = race.into();
+            self.header[slot + 10] = team;
+            self.header[slot + 11..slot + 36].fill(0);
+            self.header[slot + 11..slot + 11 + name.len()].copy_from_slice(name.as_bytes());
+            self
+        }
+
+        fn modern_section(mut self, id: &[u8; 4], data: Vec<u8>) -> Self {
+            self.modern_sections.push((*id, data));
+            self
+        }
+
+        /// Appends a frame containing a single command (player ID, command ID, then the command's
+        /// data) to the command stream.
+        fn command(mut self, frame: u32, command: &[u8]) -> Self {
+            self.commands.extend_from_slice(&frame.to_le_bytes());
+            self.commands.push(command.len() as u8);
+            self.commands.extend_from_slice(command);
+            self
+        }
+
+        /// "Compresses" data in PKWare implode format, using only literals.
+        fn implode(data: &[u8]) -> Vec<u8> {
+            let mut bits = Vec::new();
+            let mut push_bits = |value: u32, count: u32| {
+                for i in 0..count {
+                    bits.push((value >> i) & 1 == 1);
+                }
+            };
+            push_bits(0, 8); // binary literals
+            push_bits(4, 8); // dictionary size
+            for &b in data {
+                push_bits(0, 1);
+                push_bits(b as u32, 8);
+            }
+            // End of stream, a match with a length of 519
+            push_bits(1, 1);
+            push_bits(0, 7);
+            push_bits(0xff, 8);
+
+            bits.chunks(8)
+                .map(|byte| {
+                    byte.iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (i, &bit)| acc | ((bit as u8) << i))
+                })
+                .collect()
+        }
+
+        fn write_section(out: &mut Vec<u8>, data: &[u8], legacy: bool) {
+            out.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+            if data.is_empty() {
+                out.extend_from_slice(&0u32.to_le_bytes());
+                return;
+            }
+            out.extend_from_slice(&1u32.to_le_bytes());
+            if legacy {
+                let compressed = Self::implode(data);
+                out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+                out.extend_from_slice(&compressed);
+            } else if data.len() <= 4 {
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+            } else {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                std::io::Write::write_all(&mut encoder, data).unwrap();
+                let compressed = encoder.finish().unwrap();
+                out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+                out.extend_from_slice(&compressed);
+            }
+        }
+
+        /// Builds a replay in the [ReplayFormat::Modern121] format.
+        fn build(&self) -> Vec<u8> {
+            self.build_format(false)
+        }
+
+        /// Builds a replay in the [ReplayFormat::Legacy] format. Any modern sections will be
+        /// written after the legacy ones as they would be in a modern replay.
+        fn build_legacy(&self) -> Vec<u8> {
+            self.build_format(true)
+        }
+
+        fn build_format(&self, legacy: bool) -> Vec<u8> {
+            let mut out = Vec::new();
+            if legacy {
+                Self::write_section(&mut out, b"reRS", false);
+            } else {
+                Self::write_section(&mut out, b"seRS", false);
+                out.extend_from_slice(&0u32.to_le_bytes()); // offset of the modern sections, unused
+            }
+            Self::write_section(&mut out, &self.header, legacy);
+            Self::write_section(
+                &mut out,
+                &(self.commands.len() as u32).to_le_bytes(),
+                legacy,
+            );
+            Self::write_section(&mut out, &self.commands, legacy);
+            Self::write_section(
+                &mut out,
+                &(self.map_data.len() as u32).to_le_bytes(),
+                legacy,
+            );
+            Self::write_section(&mut out, &self.map_data, legacy);
+            Self::write_section(&mut out, &self.player_names, legacy);
+            for (id, data) in &self.modern_sections {
+                out.extend_from_slice(id);
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+            }
+            out
+        }
+    }
+
     #[test]
     fn test_replay_format_invalid() {
         let mut cursor = Cursor::new(NOT_A_REPLAY);
         assert!(matches!(
             Replay::new(&mut cursor),
-            Err(BroodrepError::MalformedHeader(_))
+            Err(BroodrepError::NotAReplay)
         ));
     }
 
     #[test]
-    fn test_replay_format_legacy() {
-        let mut cursor = Cursor::new(LEGACY);
-        let replay = Replay::new(&mut cursor).unwrap();
-        assert_eq!(replay.format, ReplayFormat::Legacy);
+    fn invalid_compression_type() {
+        let mut data = TestReplay::new().build_legacy();
+        assert_eq!(
+            Replay::new(Cursor::new(&data)).unwrap().format(),
+            ReplayFormat::Legacy
+        );
+
+        for (offset, value) in [(28, 2), (28, 0x77), (29, 3), (29, 7)] {
+            let original = data[offset];
+            data[offset] = value;
+            assert!(matches!(
+                Replay::new(Cursor::new(&data)),
+                Err(BroodrepError::MalformedHeader("invalid compression type"))
+            ));
+            data[offset] = original;
+        }
     }
 
     #[test]
-    fn test_replay_format_legacy_empty() {
-        let mut cursor = Cursor::new(LEGACY_EMPTY);
-        let replay = Replay::new(&mut cursor).unwrap();
-        assert_eq!(replay.format, ReplayFormat::Legacy);
+    fn require_section() {
+        let data = TestReplay::new()
+            .modern_section(b"TEST", vec![1, 2, 3])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        let test = ReplaySection::Custom(SectionTag(*b"TEST"));
+        assert_eq!(replay.require_section(test).unwrap(), [1, 2, 3]);
+        assert!(matches!(
+            replay.require_section(ReplaySection::ShieldBattery),
+            Err(BroodrepError::MissingSection(ReplaySection::ShieldBattery))
+        ));
+    }
+
+    /// Builds a version 0 ShieldBattery section with the specified user IDs.
+    fn shieldbattery_section(user_ids: [u32; 8]) -> Vec<u8> {
+        let mut data = vec![0; 0x36];
+        for id in user_ids {
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
     }
 
     #[test]
-    fn test_replay_format_scr_old() {
-        let mut cursor = Cursor::new(SCR_OLD);
-        let replay = Replay::new(&mut cursor).unwrap();
-        assert_eq!(replay.format, ReplayFormat::Modern);
+    fn all_participants() {
+        let test = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 255, PlayerType::Computer, Race::Protoss, 2, "Comp")
+            .player(3, 1, PlayerType::Human, Race::Terran, 3, "Two")
+            .player(8, 128, PlayerType::Human, Race::Random, 4, "Obs");
+        let mut replay = Replay::new(Cursor::new(test.clone().build())).unwrap();
+        assert!(!replay.is_shieldbattery());
+        let participants = replay.all_participants().unwrap();
+        assert_eq!(
+            participants
+                .iter()
+                .map(|p| (p.player.name.as_str(), p.shieldbattery_user_id))
+                .collect::<Vec<_>>(),
+            [("One", None), ("Comp", None), ("Two", None), ("Obs", None)]
+        );
+
+        let data = test
+            .modern_section(b"Sbat", shieldbattery_section([7, 0, 0, 9, 0, 0, 0, 0]))
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert!(replay.is_shieldbattery());
+        let participants = replay.all_participants().unwrap();
+        assert_eq!(
+            participants
+                .iter()
+                .map(|p| (p.player.name.as_str(), p.shieldbattery_user_id))
+                .collect::<Vec<_>>(),
+            [
+                ("One", Some(7)),
+                ("Comp", None),
+                ("Two", Some(9)),
+                ("Obs", None)
+            ]
+        );
     }
 
     #[test]
-    fn test_replay_format_scr_121() {
-        let mut cursor = Cursor::new(SCR_121);
-        let replay = Replay::new(&mut cursor).unwrap();
-        assert_eq!(replay.format, ReplayFormat::Modern121);
+    fn bugfixes() {
+        let data = TestReplay::new().build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.bugfixes().unwrap(), None);
+
+        let data = TestReplay::new()
+            .modern_section(b"BFIX", vec![1, 0, 0, 0, 0, 0, 0, 0])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.bugfixes().unwrap().map(|f| f.raw()), Some(1));
+    }
+
+    #[test]
+    fn damaged_magic() {
+        let mut data = TestReplay::new().build();
+        data[12..16].copy_from_slice(b"abcd");
+        assert!(matches!(
+            Replay::new(Cursor::new(&data)),
+            Err(BroodrepError::MalformedHeader("invalid magic bytes"))
+        ));
+
+        let data = b"This is definitely not a replay, just some text.";
+        assert!(matches!(
+            Replay::new(Cursor::new(&data)),
+            Err(BroodrepError::NotAReplay)
+        ));
+
+        for data in [&b""[..], b"short text"] {
+            assert!(matches!(
+                Replay::new(Cursor::new(data)),
+                Err(BroodrepError::NotAReplay)
+            ));
+        }
+    }
+
+    #[test]
+    fn detect_format_from_prefix() {
+        let data = TestReplay::new().build();
+        assert_eq!(
+            detect_replay_format(&data[..FORMAT_DETECTION_LEN]).unwrap(),
+            ReplayFormat::Modern121
+        );
+        let data = TestReplay::new().build_legacy();
+        assert_eq!(
+            detect_replay_format(&data[..FORMAT_DETECTION_LEN]).unwrap(),
+            ReplayFormat::Legacy
+        );
+        assert!(matches!(
+            detect_replay_format(&b"This is definitely not a replay"[..FORMAT_DETECTION_LEN]),
+            Err(BroodrepError::NotAReplay)
+        ));
+        assert!(matches!(
+            detect_replay_format(&data[..20]),
+            Err(BroodrepError::MalformedHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_replay_built() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 2, "Two")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Modern121);
+        assert_eq!(replay.header.frames, 1000);
+        assert_eq!(replay.duration(), std::time::Duration::from_secs(42));
+        assert_eq!(
+            replay.duration_at_speed(GameSpeed::Normal),
+            std::time::Duration::from_secs(67)
+        );
+        assert_eq!(replay.header.map_name, "Test Map");
+        assert_eq!(replay.players().count(), 2);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .build();
+        let replay = Replay::from_slice(&data).unwrap();
+        assert_eq!(replay.header.slots[0].name, "One");
+        let replay = Replay::from_bytes(data).unwrap();
+        assert_eq!(replay.header.slots[0].name, "One");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn to_json() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 2, "Two \"2\"")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        let json = replay.to_json().unwrap();
+        assert!(json.starts_with("{\"format\":\"Modern121\",\"duration_ms\":42000,\"header\":{"));
+        for key in [
+            "\"engine\":",
+            "\"frames\":1000",
+            "\"map_name\":\"Test Map\"",
+            "\"slots\":[{",
+            "\"players\":[{",
+            "\"race\":\"Zerg\"",
+            "\"name\":\"Two \\\"2\\\"\"",
+            "\"observers\":[]",
+        ] {
+            assert!(json.contains(key), "{key} missing from {json}");
+        }
+    }
+
+    #[test]
+    fn legacy_trailing_data() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .modern_section(b"XTRA", vec![1, 2, 3])
+            .build_legacy();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Legacy);
+        assert_eq!(replay.header.slots[0].name, "One");
+        assert_eq!(
+            replay
+                .get_raw_section(ReplaySection::Custom(SectionTag(*b"XTRA")))
+                .unwrap(),
+            Some(vec![1, 2, 3])
+        );
+
+        assert_eq!(replay.trailing_data(), None);
+
+        let data = TestReplay::new()
+            .modern_section(b"XTRA", vec![1, 2, 3])
+            .modern_section(b"XTRA", vec![4, 5, 6])
+            .build_legacy();
+        let len = data.len() as u64;
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(
+            replay
+                .get_raw_section(ReplaySection::Custom(SectionTag(*b"XTRA")))
+                .unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(replay.trailing_data(), Some(len - 11..len));
+
+        // Too short to be a section
+        let mut data = TestReplay::new().build_legacy();
+        data.extend_from_slice(&[1, 2, 3]);
+        let len = data.len() as u64;
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.trailing_data(), Some(len - 3..len));
+        assert!(replay.custom_section_ids().is_empty());
+
+        // A section tag followed by a size that extends past the end of the file
+        let mut data = TestReplay::new().build_legacy();
+        data.extend_from_slice(b"BOGS");
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.extend_from_slice(&[1, 2]);
+        let len = data.len() as u64;
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.trailing_data(), Some(len - 10..len));
+        assert!(!replay.has_section(ReplaySection::Custom(SectionTag(*b"BOGS"))));
+        assert!(replay.missing_required_sections().is_empty());
+    }
+
+    #[test]
+    fn player_type_round_trip() {
+        for value in 0..=u8::MAX {
+            assert_eq!(u8::from(PlayerType::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn unknown_header_values() {
+        let mut replay = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Unknown(12), Race::Unknown(9), 2, "Two");
+        replay.header[58] = 9;
+        let data = replay.build();
+
+        let parsed = Replay::new(Cursor::new(data.clone())).unwrap();
+        assert_eq!(parsed.game_speed(), GameSpeed::Unknown(9));
+        assert_eq!(parsed.slots()[1].player_type, PlayerType::Unknown(12));
+        assert_eq!(parsed.slots()[1].race, Race::Unknown(9));
+        assert_eq!(parsed.slots()[1].race.to_string(), "Unknown (9)");
+
+        let strict = ParseConfig {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Replay::new_with_config(Cursor::new(data), strict.clone()),
+            Err(BroodrepError::MalformedHeader("invalid game speed"))
+        ));
+
+        let mut replay = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Unknown(12), Race::Protoss, 2, "Two");
+        let data = replay.build();
+        assert!(matches!(
+            Replay::new_with_config(Cursor::new(data), strict.clone()),
+            Err(BroodrepError::MalformedHeader("invalid player type"))
+        ));
+
+        replay = replay.player(1, 1, PlayerType::Computer, Race::Protoss, 2, "Two");
+        assert!(Replay::new_with_config(Cursor::new(replay.build()), strict).is_ok());
+    }
+
+    #[test]
+    fn slot_byte_ranges() {
+        assert_eq!(ReplayHeader::slot_byte_range(0), 0xa1..0xc5);
+        assert_eq!(ReplayHeader::slot_byte_range(11), 0x22d..0x251);
+
+        let mut replay = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 2, "Two");
+        let range = ReplayHeader::slot_byte_range(1);
+        let slot = &mut replay.header[range];
+        slot[0x09] = 1; // Terran
+        slot[0x0b..0x0b + 5].copy_from_slice(b"Four\0");
+
+        let parsed = parse_replay_header(&replay.header).unwrap();
+        assert_eq!(parsed.slots[0].name, "One");
+        assert_eq!(parsed.slots[1].name, "Four");
+        assert_eq!(parsed.slots[1].race, Race::Terran);
+        assert_eq!(parsed.slots[1].team, 2);
+    }
+
+    #[test]
+    fn summary_from_parts() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "Player")
+            .modern_section(b"TEST", vec![1, 2, 3])
+            .build();
+        let replay = Replay::new(Cursor::new(data.clone())).unwrap();
+        let summary = replay.summary();
+        drop(replay.into_inner());
+
+        let mut replay = Replay::from_parts(Cursor::new(data.clone()), summary.clone()).unwrap();
+        assert_eq!(replay.header.slots[0].name, "Player");
+        assert_eq!(
+            replay
+                .get_raw_section(ReplaySection::Custom(SectionTag(*b"TEST")))
+                .unwrap(),
+            Some(vec![1, 2, 3])
+        );
+
+        let mut truncated = data.clone();
+        truncated.pop();
+        assert!(matches!(
+            Replay::from_parts(Cursor::new(truncated), summary.clone()),
+            Err(BroodrepError::MalformedHeader(_))
+        ));
+        let mut wrong_format = data;
+        wrong_format[12..16].copy_from_slice(b"reRS");
+        assert!(Replay::from_parts(Cursor::new(wrong_format), summary).is_err());
+    }
+
+    #[test]
+    fn chunk_layout() {
+        let data = TestReplay::new()
+            .modern_section(b"TEST", vec![1, 2, 3])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data.clone())).unwrap();
+
+        let header = replay.section_chunk_layout(ReplaySection::Header).unwrap();
+        assert_eq!(header.len(), 1);
+        let chunk = header[0];
+        let chunk_data = &data[chunk.offset as usize..][..chunk.compressed_size as usize];
+        assert_eq!(chunk_data[0], 0x78);
+
+        assert_eq!(
+            replay
+                .section_chunk_layout(ReplaySection::Custom(SectionTag(*b"TEST")))
+                .unwrap(),
+            [ChunkInfo {
+                offset: data.len() as u64 - 3,
+                compressed_size: 3,
+            }]
+        );
+        assert_eq!(
+            replay.section_chunk_layout(ReplaySection::Gcfg).unwrap(),
+            []
+        );
+    }
+
+    fn read_modern_chunk(chunk: &[u8]) -> Vec<u8> {
+        read_modern_chunk_with_config(chunk, DecompressionConfig::default()).unwrap()
+    }
+
+    fn read_modern_chunk_with_config(
+        chunk: &[u8],
+        config: DecompressionConfig,
+    ) -> Result<Vec<u8>, BroodrepError> {
+        let mut section = Vec::new();
+        section.extend_from_slice(&0u32.to_le_bytes());
+        section.extend_from_slice(&1u32.to_le_bytes());
+        section.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        section.extend_from_slice(chunk);
+        let len = section.len() as u64;
+        Replay::<Cursor<Vec<u8>>>::read_legacy_section(
+            &mut Cursor::new(section),
+            len,
+            ReplayFormat::Modern121,
+            config,
+            None,
+            &mut 0,
+            &mut Vec::new(),
+        )
+    }
+
+    #[test]
+    fn modern_chunk_compressed() {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"hello hello hello").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(read_modern_chunk(&compressed), b"hello hello hello");
+    }
+
+    #[test]
+    fn modern_chunk_uncompressed() {
+        assert_eq!(read_modern_chunk(&[1, 2, 3, 4, 5, 6]), [1, 2, 3, 4, 5, 6]);
+        // Starts with 0x78 but has an invalid header checksum
+        assert_eq!(
+            read_modern_chunk(&[0x78, 0, 1, 2, 3, 4]),
+            [0x78, 0, 1, 2, 3, 4]
+        );
+        // Has a valid zlib header, but isn't actually zlib data
+        assert_eq!(
+            read_modern_chunk(&[0x78, 0x01, 0xff, 0xff, 0xff, 0xff]),
+            [0x78, 0x01, 0xff, 0xff, 0xff, 0xff]
+        );
+        // Too short to be zlib data
+        assert_eq!(read_modern_chunk(&[0x78, 0x9c]), [0x78, 0x9c]);
+    }
+
+    #[test]
+    fn modern_chunk_uncompressed_size_limit() {
+        let config = DecompressionConfig {
+            max_total_decompressed_size: Some(4),
+            ..Default::default()
+        };
+        for chunk in [
+            &[1, 2, 3, 4, 5, 6][..],
+            // Looks like zlib data until it fails to decode, and is then treated as uncompressed
+            &[0x78, 0x01, 0xff, 0xff, 0xff, 0xff][..],
+        ] {
+            let Err(BroodrepError::IoError(err)) = read_modern_chunk_with_config(chunk, config)
+            else {
+                panic!("expected an error for {chunk:?}");
+            };
+            assert!(matches!(
+                err.downcast::<DecompressionError>(),
+                Ok(DecompressionError::SizeLimitExceeded)
+            ));
+        }
+        assert_eq!(
+            read_modern_chunk_with_config(&[0x78, 0x01, 0xff, 0xff], config).unwrap(),
+            [0x78, 0x01, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn section_tags() {
+        let tag: SectionTag = "Sbat".parse().unwrap();
+        assert_eq!(tag, SectionTag(*b"Sbat"));
+        assert_eq!(tag.to_string(), "Sbat");
+        assert_eq!(u32::from(tag), 0x74616253);
+        assert_eq!(SectionTag::from(0x74616253), tag);
+        assert_eq!(ReplaySection::from(tag), ReplaySection::ShieldBattery);
+        assert_eq!(ReplaySection::ShieldBattery.tag(), Some(tag));
+        assert_eq!(ReplaySection::Commands.tag(), None);
+        for section in [
+            ReplaySection::Skins,
+            ReplaySection::Limits,
+            ReplaySection::Bfix,
+            ReplaySection::CustomColors,
+            ReplaySection::Gcfg,
+        ] {
+            assert_eq!(section.tag().map(ReplaySection::from), Some(section));
+        }
+
+        let tag = SectionTag([b'A', 0, 0xff, b'\\']);
+        assert_eq!(tag.to_string(), "A\\x00\\xff\\\\");
+        assert_eq!(format!("{tag:?}"), "SectionTag(\"A\\x00\\xff\\\\\")");
+        assert_eq!(
+            ReplaySection::from(tag),
+            ReplaySection::Custom(SectionTag([b'A', 0, 0xff, b'\\']))
+        );
+
+        assert!("ABC".parse::<SectionTag>().is_err());
+        assert!("ABCDE".parse::<SectionTag>().is_err());
+    }
+
+    #[test]
+    fn allied_players() {
+        let mut replay = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 1, "Two")
+            .player(2, 2, PlayerType::Human, Race::Terran, 2, "Three");
+        let header = parse_replay_header(&replay.header).unwrap();
+        let [one, two, _] = &header.slots[..3] else {
+            unreachable!()
+        };
+        // Melee games don't have teams
+        assert!(!header.are_allied(one, two));
+
+        replay.header[60] = 15; // Top vs Bottom
+        let header = parse_replay_header(&replay.header).unwrap();
+        let [one, two, three] = &header.slots[..3] else {
+            unreachable!()
+        };
+        assert!(header.are_allied(one, two));
+        assert!(header.are_allied(two, one));
+        assert!(!header.are_allied(one, three));
+        assert!(!header.are_allied(one, one));
+        assert!(!header.are_allied(one, &header.slots[3]));
+    }
+
+    #[test]
+    fn turn_rate() {
+        let replay = TestReplay::new();
+        let data = replay.build();
+        assert_eq!(
+            Replay::new(Cursor::new(data)).unwrap().turn_rate().unwrap(),
+            None
+        );
+
+        let data = replay
+            .command(10, &[0, 0x55, 1])
+            .command(20, &[1, 0x55, 2])
+            .command(30, &[0, 0x1f, 7, 0])
+            .build();
+        assert_eq!(
+            Replay::new(Cursor::new(data)).unwrap().turn_rate().unwrap(),
+            Some(TurnRate::ExtraHigh)
+        );
+    }
+
+    #[test]
+    fn commands_by_player() {
+        let data = TestReplay::new()
+            .command(10, &[0, 0x55, 1])
+            .command(20, &[1, 0x55, 2])
+            .command(30, &[0, 0x1f, 7, 0])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+
+        let grouped = replay.commands_by_player().unwrap();
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(
+            grouped[&0].iter().map(|c| c.frame).collect::<Vec<_>>(),
+            [10, 30]
+        );
+        assert_eq!(
+            grouped[&1].iter().map(|c| c.frame).collect::<Vec<_>>(),
+            [20]
+        );
+    }
+
+    #[test]
+    fn control_group_stats() {
+        let data = TestReplay::new()
+            .command(10, &[0, 0x13, 0, 1])
+            .command(20, &[0, 0x13, 1, 1])
+            .command(30, &[0, 0x13, 1, 1])
+            .command(40, &[0, 0x13, 2, 2])
+            .command(50, &[1, 0x13, 1, 5])
+            .command(55, &[1, 0x13, 1, 10])
+            .command(60, &[1, 0x13, 7, 6])
+            .command(70, &[2, 0x13, 7, 6])
+            .command(80, &[3, 0x13, 0, 200])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+
+        let stats = replay.control_group_stats().unwrap();
+        assert_eq!(
+            stats[&0],
+            ControlGroupStats {
+                assigns: 1,
+                adds: 1,
+                selects: 2,
+                groups_used: 2,
+            }
+        );
+        assert_eq!(
+            stats[&1],
+            ControlGroupStats {
+                assigns: 0,
+                adds: 0,
+                selects: 1,
+                groups_used: 1,
+            }
+        );
+        assert!(!stats.contains_key(&2));
+        assert!(!stats.contains_key(&3));
+    }
+
+    #[test]
+    fn sync_frames() {
+        let data = TestReplay::new()
+            .command(10, &[0, 0x37, 1, 2, 3, 4, 5, 6])
+            .command(12, &[1, 0x05])
+            .command(20, &[1, 0x37, 6, 5, 4, 3, 2, 1])
+            .build();
+        let mut replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(
+            replay.sync_frames().unwrap(),
+            [
+                SyncFrame {
+                    frame: 10,
+                    player_id: 0,
+                    data: vec![1, 2, 3, 4, 5, 6],
+                },
+                SyncFrame {
+                    frame: 20,
+                    player_id: 1,
+                    data: vec![6, 5, 4, 3, 2, 1],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_encodings() {
+        let mut replay = TestReplay::new().player(0, 0, PlayerType::Human, Race::Zerg, 1, "");
+        // "Вася" in Windows-1251
+        replay.header[161 + 11..161 + 15].copy_from_slice(&[0xC2, 0xE0, 0xF1, 0xFF]);
+        // A map string table containing "Вася" as well
+        replay.map_data = [
+            &b"STR "[..],
+            &10u32.to_le_bytes(),
+            &1u16.to_le_bytes(),
+            &4u16.to_le_bytes(),
+            &[0xC2, 0xE0, 0xF1, 0xFF, 0],
+        ]
+        .concat();
+        let data = replay.build();
+
+        let replay = Replay::new(Cursor::new(&data)).unwrap();
+        // With the cp949 feature, this decodes to Hanja instead
+        #[cfg(not(feature = "cp949"))]
+        assert_eq!(replay.header.slots[0].name, "\u{fffd}".repeat(4));
+        assert_eq!(replay.header.slots[0].raw_name, [0xC2, 0xE0, 0xF1, 0xFF]);
+        assert_eq!(replay.header.raw_map_name, b"Test Map");
+
+        let config = ParseConfig {
+            fallback_encodings: vec![TextEncoding::Windows1251],
+            ..Default::default()
+        };
+        let mut replay = Replay::new_with_config(Cursor::new(&data), config.clone()).unwrap();
+        assert_eq!(replay.header.slots[0].name, "Вася");
+        assert_eq!(replay.header.map_name, "Test Map");
+        // Strings in sections that are parsed later use the same settings
+        let map = replay.map_data().unwrap().unwrap();
+        assert_eq!(map.string(1).as_deref(), Some("Вася"));
+        let map = replay.resolve().unwrap().map_data().unwrap().unwrap();
+        assert_eq!(map.string(1).as_deref(), Some("Вася"));
+
+        let config = ParseConfig {
+            string_encoding: StringEncoding::Utf8Lossy,
+            ..config
+        };
+        let replay = Replay::new_with_config(Cursor::new(&data), config).unwrap();
+        assert_eq!(replay.header.slots[0].name, "\u{fffd}".repeat(4));
+    }
+
+    #[test]
+    fn resolve() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ResolvedReplay>();
+
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Terran, 1, "Player")
+            .modern_section(b"TEST", vec![1, 2, 3])
+            .command(10, &[0, 0x1f, 7, 0])
+            .command(20, &[0, 0x55, 1])
+            .build();
+        let mut replay = Replay::new(Cursor::new(&data)).unwrap();
+        let expected_commands = replay.commands().unwrap();
+        let resolved = replay.resolve().unwrap();
+
+        assert_eq!(resolved.header.slots[0].name, "Player");
+        assert_eq!(resolved.commands(), expected_commands);
+        assert_eq!(resolved.turn_rate(), Some(TurnRate::High));
+        assert_eq!(
+            resolved.get_raw_section(ReplaySection::Custom(SectionTag(*b"TEST"))),
+            Some(&[1, 2, 3][..])
+        );
+        assert!(resolved.get_raw_section(ReplaySection::Header).is_some());
+        assert!(
+            resolved
+                .get_raw_section(ReplaySection::ShieldBattery)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn build_order_similarity() {
+        fn build(players: &[(u8, Race)], commands: &[(u8, u8)]) -> Vec<u8> {
+            let mut replay = TestReplay::new();
+            for (i, &(network_id, race)) in players.iter().enumerate() {
+                replay = replay.player(i, network_id, PlayerType::Human, race, 1, "Player");
+            }
+            for (frame, &(player_id, unit_type)) in commands.iter().enumerate() {
+                replay
+                    .commands
+                    .extend_from_slice(&(frame as u32 * 10).to_le_bytes());
+                replay
+                    .commands
+                    .extend_from_slice(&[4, player_id, 0x1f, unit_type, 0]);
+            }
+            replay.build()
+        }
+
+        let a = build(
+            &[(0, Race::Terran), (1, Race::Zerg)],
+            &[(0, 7), (1, 41), (0, 7), (1, 41)],
+        );
+        let b = build(
+            &[(0, Race::Zerg), (1, Race::Terran)],
+            &[(0, 41), (1, 7), (0, 41), (1, 1)],
+        );
+        let c = build(&[(0, Race::Protoss)], &[(0, 64)]);
+
+        let mut a = Replay::new(Cursor::new(a)).unwrap();
+        let mut b = Replay::new(Cursor::new(b)).unwrap();
+        let mut c = Replay::new(Cursor::new(c)).unwrap();
+        assert_eq!(
+            super::build_order_similarity(&mut a, &mut b).unwrap(),
+            Some(0.75)
+        );
+        assert_eq!(super::build_order_similarity(&mut a, &mut c).unwrap(), None);
+    }
+
+    #[test]
+    fn missing_required_sections() {
+        let mut replay = TestReplay::new();
+        replay.commands = vec![0; 64];
+        replay.map_data = vec![0; 64];
+        let data = replay.build();
+        let parsed = Replay::new(Cursor::new(&data)).unwrap();
+        assert_eq!(parsed.missing_required_sections(), []);
+
+        let layout = Replay::new(Cursor::new(&data))
+            .unwrap()
+            .section_chunk_layout(ReplaySection::MapData)
+            .unwrap();
+        let map_start = layout[0].offset as usize;
+        // Cut off partway through the map data
+        let parsed = Replay::new(Cursor::new(&data[..map_start + 4])).unwrap();
+        assert_eq!(
+            parsed.missing_required_sections(),
+            [ReplaySection::MapData, ReplaySection::PlayerNames]
+        );
+        // Cut off right after the header
+        let header_layout = Replay::new(Cursor::new(&data))
+            .unwrap()
+            .section_chunk_layout(ReplaySection::Header)
+            .unwrap();
+        let header_end =
+            (header_layout[0].offset + header_layout[0].compressed_size as u64) as usize;
+        let parsed = Replay::new(Cursor::new(&data[..header_end])).unwrap();
+        assert_eq!(
+            parsed.missing_required_sections(),
+            [
+                ReplaySection::Commands,
+                ReplaySection::MapData,
+                ReplaySection::PlayerNames
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_ordered() {
+        let data = TestReplay::new()
+            .modern_section(b"TES2", vec![1, 2, 3])
+            .modern_section(b"TES1", vec![4])
+            .build();
+        let replay = Replay::new(Cursor::new(&data)).unwrap();
+        let sections = replay.sections_ordered();
+        assert_eq!(
+            sections.iter().map(|&(s, _)| s).collect::<Vec<_>>(),
+            [
+                ReplaySection::Header,
+                ReplaySection::Commands,
+                ReplaySection::MapData,
+                ReplaySection::PlayerNames,
+                ReplaySection::Custom(SectionTag(*b"TES2")),
+                ReplaySection::Custom(SectionTag(*b"TES1")),
+            ]
+        );
+        assert!(replay.has_section(ReplaySection::Custom(SectionTag(*b"TES1"))));
+        assert!(!replay.has_section(ReplaySection::Custom(SectionTag(*b"TES3"))));
+        assert_eq!(replay.custom_section_ids(), [*b"TES2", *b"TES1"]);
+        assert_eq!(
+            replay.section_layout(),
+            [(*b"TES2", sections[4].1), (*b"TES1", sections[5].1)]
+        );
+        let mut unordered = replay.sections().collect::<Vec<_>>();
+        unordered.sort_by_key(|s| sections.iter().position(|&(o, _)| o == *s));
+        assert_eq!(
+            unordered,
+            sections.iter().map(|&(s, _)| s).collect::<Vec<_>>()
+        );
+
+        let (_, last_offset) = sections[5];
+        assert_eq!(
+            &data[last_offset as usize - 4..last_offset as usize],
+            b"TES1"
+        );
+        assert_eq!(
+            replay
+                .size_breakdown()
+                .iter()
+                .map(|&(s, _)| s)
+                .collect::<Vec<_>>(),
+            sections.iter().map(|&(s, _)| s).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn player_colors() {
+        let data = TestReplay::new().build();
+        let replay = Replay::new(Cursor::new(&data)).unwrap();
+        assert_eq!(
+            replay.player_color(0),
+            Some(PlayerColor { r: 244, g: 4, b: 4 })
+        );
+        assert_eq!(replay.player_color(11), Some(STANDARD_PLAYER_COLORS[11]));
+        assert_eq!(replay.player_color(12), None);
+
+        let data = TestReplay::new()
+            .modern_section(b"CCLR", vec![0; SIZE_CUSTOM_COLORS])
+            .build();
+        let replay = Replay::new(Cursor::new(&data)).unwrap();
+        assert_eq!(replay.player_color(0), None);
+    }
+
+    #[test]
+    fn total_decompression_limit() {
+        let mut replay = TestReplay::new();
+        replay.commands = vec![0; 64];
+        let data = replay.build();
+        let config = ParseConfig {
+            decompression: DecompressionConfig {
+                max_total_decompressed_size: Some((SIZE_HEADER + 100) as u64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut replay = Replay::new_with_config(Cursor::new(&data), config.clone()).unwrap();
+        assert!(replay.get_raw_section(ReplaySection::Commands).is_ok());
+        let err = replay.get_raw_section(ReplaySection::Commands).unwrap_err();
+        let BroodrepError::IoError(err) = err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert!(matches!(
+            err.downcast::<DecompressionError>(),
+            Ok(DecompressionError::SizeLimitExceeded)
+        ));
+
+        let config = ParseConfig {
+            decompression: DecompressionConfig {
+                max_total_decompressed_size: Some(SIZE_HEADER as u64 - 1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(Replay::new_with_config(Cursor::new(&data), config).is_err());
+    }
+
+    #[test]
+    fn duplicate_sections() {
+        let data = TestReplay::new()
+            .modern_section(b"XTRA", vec![1, 2, 3])
+            .modern_section(b"XTRA", vec![4, 5, 6])
+            .modern_section(b"LAST", vec![7])
+            .build();
+        assert!(matches!(
+            Replay::new(Cursor::new(&data)),
+            Err(BroodrepError::DuplicateSection(ReplaySection::Custom(SectionTag(t)))) if &t == b"XTRA"
+        ));
+
+        for (policy, expected) in [
+            (DuplicateSectionPolicy::KeepFirst, vec![1, 2, 3]),
+            (DuplicateSectionPolicy::KeepLast, vec![4, 5, 6]),
+        ] {
+            let config = ParseConfig {
+                duplicate_sections: policy,
+                ..Default::default()
+            };
+            let mut replay = Replay::new_with_config(Cursor::new(&data), config).unwrap();
+            assert_eq!(
+                replay
+                    .get_raw_section(ReplaySection::Custom(SectionTag(*b"XTRA")))
+                    .unwrap(),
+                Some(expected)
+            );
+            assert_eq!(
+                replay
+                    .get_raw_section(ReplaySection::Custom(SectionTag(*b"LAST")))
+                    .unwrap(),
+                Some(vec![7])
+            );
+        }
+    }
+
+    #[test]
+    fn verify_checksums() {
+        let mut replay = TestReplay::new();
+        replay.commands = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let mut data = replay.build();
+
+        let mut parsed = Replay::new(Cursor::new(&data)).unwrap();
+        let report = parsed.verify_checksums().unwrap();
+        assert!(report.is_valid());
+        assert_eq!(
+            report
+                .sections
+                .iter()
+                .map(|s| s.section)
+                .collect::<Vec<_>>(),
+            [
+                ReplaySection::Header,
+                ReplaySection::Commands,
+                ReplaySection::MapData,
+                ReplaySection::PlayerNames,
+            ]
+        );
+
+        let offset = parsed.section_offsets[&ReplaySection::Commands] as usize;
+        data[offset] ^= 0xff;
+        let mut parsed = Replay::new(Cursor::new(&data)).unwrap();
+        let report = parsed.verify_checksums().unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.mismatches().map(|s| s.section).collect::<Vec<_>>(),
+            [ReplaySection::Commands]
+        );
+
+        data.truncate(offset + 12);
+        let mut parsed = Replay::new(Cursor::new(&data)).unwrap();
+        let report = parsed.verify_checksums().unwrap();
+        assert_eq!(
+            report.mismatches().copied().collect::<Vec<_>>(),
+            [SectionChecksum {
+                section: ReplaySection::Commands,
+                expected: Some(crc32fast::hash(&[1, 2, 3, 4, 5, 6, 7, 8]) ^ 0xff),
+                actual: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn verify_checksums_fixtures() {
+        // These were written by StarCraft itself, so unlike the synthetic replays above, they show
+        // that the stored checksums really are a CRC-32 of the decompressed data
+        for data in [LEGACY, LEGACY_EMPTY, SCR_OLD, SCR_121, SB_DATA] {
+            let mut replay = Replay::new(Cursor::new(data)).unwrap();
+            let report = replay.verify_checksums().unwrap();
+            assert_eq!(report.sections.len(), 4);
+            assert!(report.is_valid(), "{report:?}");
+        }
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut replay = TestReplay::new();
+        replay.commands = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let modern = replay.build();
+        let legacy = replay.build_legacy();
+        assert_ne!(modern, legacy);
+
+        let fingerprint = Replay::new(Cursor::new(&modern))
+            .unwrap()
+            .fingerprint()
+            .unwrap();
+        assert_eq!(
+            Replay::new(Cursor::new(&legacy))
+                .unwrap()
+                .fingerprint()
+                .unwrap(),
+            fingerprint
+        );
+
+        replay.commands[0] = 0;
+        assert_ne!(
+            Replay::new(Cursor::new(replay.build()))
+                .unwrap()
+                .fingerprint()
+                .unwrap(),
+            fingerprint
+        );
+    }
+
+    #[test]
+    fn map_hash() {
+        const MAP_DATA_SHA1: [u8; 20] = [
+            0x7b, 0x08, 0x5c, 0x59, 0x66, 0xa5, 0xe7, 0x34, 0x62, 0xd7, 0x12, 0xc0, 0x54, 0xf5,
+            0xa4, 0x06, 0x3f, 0xf2, 0x35, 0x59,
+        ];
+        let mut replay = TestReplay::new();
+        replay.map_data = b"VER \x02\x00\x00\x00\xcd\x00".to_vec();
+        let mut parsed = Replay::new(Cursor::new(replay.build())).unwrap();
+        assert_eq!(parsed.map_hash().unwrap(), Some(MAP_DATA_SHA1));
+        let mut parsed = Replay::new(Cursor::new(replay.build_legacy())).unwrap();
+        assert_eq!(parsed.map_hash().unwrap(), Some(MAP_DATA_SHA1));
+    }
+
+    #[test]
+    fn parse_dir() {
+        let dir = std::env::temp_dir().join(format!("broodrep-parse-dir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested.rep")).unwrap();
+        let replay = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .build();
+        std::fs::write(dir.join("b.REP"), &replay).unwrap();
+        std::fs::write(dir.join("a.rep"), &replay).unwrap();
+        std::fs::write(dir.join("bad.rep"), b"not a replay").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not a replay either").unwrap();
+
+        let results = batch::parse_dir(&dir).unwrap().collect::<Vec<_>>();
+        let paths = batch::replay_paths(&dir).unwrap();
+        #[cfg(feature = "rayon")]
+        let par_results = {
+            use rayon::iter::ParallelIterator as _;
+            batch::par_parse_dir(&dir).unwrap().collect::<Vec<_>>()
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            paths,
+            results
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(path, _)| path.file_name().unwrap().to_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["a.rep", "b.REP", "bad.rep"]
+        );
+        assert_eq!(results[0].1.as_ref().unwrap().header.slots[0].name, "One");
+        assert!(results[1].1.is_ok());
+        assert!(results[2].1.is_err());
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            par_results
+                .iter()
+                .map(|(path, replay)| (path, replay.is_ok()))
+                .collect::<Vec<_>>(),
+            results
+                .iter()
+                .map(|(path, replay)| (path, replay.is_ok()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn slot_counts() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Computer, Race::Protoss, 2, "Comp")
+            .player(2, 2, PlayerType::Human, Race::Terran, 2, "Two")
+            .player(3, 128, PlayerType::Human, Race::Random, 3, "Obs")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert_eq!(replay.occupied_slot_count(), 4);
+        assert_eq!(replay.human_player_count(), 2);
+
+        let replay = Replay::new(Cursor::new(TestReplay::new().build())).unwrap();
+        assert_eq!(replay.occupied_slot_count(), 0);
+        assert_eq!(replay.human_player_count(), 0);
+    }
+
+    #[test]
+    fn multiplayer_detection() {
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Computer, Race::Protoss, 2, "Comp")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert!(!replay.is_multiplayer());
+        assert!(replay.has_computer_players());
+
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Computer, Race::Protoss, 2, "Comp")
+            .player(2, 128, PlayerType::Human, Race::Random, 3, "Obs")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert!(replay.is_multiplayer());
+        assert!(replay.has_computer_players());
+
+        let data = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 2, "Two")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+        assert!(replay.is_multiplayer());
+        assert!(!replay.has_computer_players());
+    }
+
+    #[test]
+    fn player_for_command() {
+        let data = TestReplay::new()
+            .player(0, 1, PlayerType::Human, Race::Zerg, 1, "One")
+            .player(1, 255, PlayerType::Computer, Race::Protoss, 2, "Comp")
+            .player(2, 0, PlayerType::Human, Race::Terran, 3, "Two")
+            .player(3, 128, PlayerType::Human, Race::Random, 4, "Obs")
+            .build();
+        let replay = Replay::new(Cursor::new(data)).unwrap();
+
+        let name = |id| replay.player_for_command(id).map(|p| p.name.as_str());
+        assert_eq!(name(0), Some("Two"));
+        assert_eq!(name(1), Some("One"));
+        assert_eq!(name(128), Some("Obs"));
+        assert_eq!(name(2), None);
+        assert_eq!(name(255), None);
+    }
+
+    #[test]
+    fn frame_time_conversion() {
+        let mut test = TestReplay::new();
+        let replay = Replay::new(Cursor::new(test.build())).unwrap();
+        assert_eq!(replay.frame_to_time(0), std::time::Duration::ZERO);
+        assert_eq!(
+            replay.frame_to_time(24),
+            std::time::Duration::from_millis(1008)
+        );
+        assert_eq!(
+            replay.time_to_frame(std::time::Duration::from_millis(1008)),
+            24
+        );
+        assert_eq!(
+            replay.time_to_frame(std::time::Duration::from_millis(1049)),
+            24
+        );
+        assert_eq!(replay.time_to_frame(std::time::Duration::MAX), u32::MAX);
+
+        test.header[58] = 3; // Normal
+        let replay = Replay::new(Cursor::new(test.build())).unwrap();
+        assert_eq!(
+            replay.frame_to_time(15),
+            std::time::Duration::from_millis(1005)
+        );
+        assert_eq!(replay.time_to_frame(std::time::Duration::from_secs(1)), 14);
+    }
+
+    #[test]
+    fn team_layout() {
+        let mut test = TestReplay::new()
+            .player(0, 0, PlayerType::Human, Race::Zerg, 2, "One")
+            .player(1, 1, PlayerType::Human, Race::Protoss, 1, "Two")
+            .player(2, 2, PlayerType::Human, Race::Terran, 2, "Three")
+            .player(3, 3, PlayerType::Human, Race::Terran, 1, "Four")
+            .player(4, 128, PlayerType::Human, Race::Random, 3, "Obs");
+        let replay = Replay::new(Cursor::new(test.clone().build())).unwrap();
+        assert_eq!(replay.team_layout(), TeamLayout::None);
+
+        test.header[60..62].copy_from_slice(&11u16.to_le_bytes()); // Team Melee
+        test.header[62..64].copy_from_slice(&2u16.to_le_bytes());
+        let replay = Replay::new(Cursor::new(test.clone().build())).unwrap();
+        assert_eq!(
+            replay.team_layout(),
+            TeamLayout::TeamGame {
+                slots_per_team: 2,
+                teams: vec![vec![1, 3], vec![0, 2]],
+            }
+        );
+
+        test.header[60..62].copy_from_slice(&15u16.to_le_bytes()); // Top vs Bottom
+        test.header[62..64].copy_from_slice(&3u16.to_le_bytes());
+        let replay = Replay::new(Cursor::new(test.build())).unwrap();
+        assert_eq!(
+            replay.team_layout(),
+            TeamLayout::TopVsBottom {
+                top_count: 3,
+                teams: vec![vec![1, 3], vec![0, 2]],
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_header() {
+        let data = TestReplay::new().build();
+        for (len, expected) in [
+            (14, "truncated header: magic bytes"),
+            (18, "truncated header: section offset"),
+            (20, "truncated header: header section"),
+            (26, "truncated header: header section"),
+            (30, "truncated header: header section"),
+        ] {
+            let result = Replay::new(Cursor::new(&data[..len]));
+            assert!(
+                matches!(result, Err(BroodrepError::MalformedHeader(message)) if message == expected),
+                "{len}: {:?}",
+                result.err()
+            );
+        }
+        // Without the start of the magic chunk, there's nothing to show this was ever a replay
+        for len in [0, 8] {
+            assert!(matches!(
+                Replay::new(Cursor::new(&data[..len])),
+                Err(BroodrepError::NotAReplay)
+            ));
+        }
+
+        // Chunks that extend past the end of the file are reported the same way as in any other
+        // section
+        assert!(matches!(
+            Replay::new(Cursor::new(&data[..40])),
+            Err(BroodrepError::MalformedSection(_))
+        ));
+    }
+
+    #[test]
+    fn parse_header_too_short() {
+        let replay = TestReplay::new();
+        assert!(parse_replay_header(&replay.header[..MIN_SIZE_HEADER]).is_ok());
+        assert!(matches!(
+            parse_replay_header(&replay.header[..MIN_SIZE_HEADER - 1]),
+            Err(BroodrepError::MalformedHeader(_))
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn slot_byte_range_out_of_bounds() {
+        ReplayHeader::slot_byte_range(12);
+    }
+
+    #[test]
+    fn oversized_chunk() {
+        let mut data = TestReplay::new()
+            .modern_section(b"Sbat", vec![0; 32])
+            .build();
+        let len = data.len();
+        // The size of the ShieldBattery section
+        data[len - 36..len - 32].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut replay = Replay::new(Cursor::new(data.clone())).unwrap();
+        assert!(matches!(
+            replay.get_raw_section(ReplaySection::ShieldBattery),
+            Err(BroodrepError::MalformedSection(_))
+        ));
+
+        // The size of the header's only chunk
+        data[28..32].copy_from_slice(&(u32::MAX - 8).to_le_bytes());
+        assert!(matches!(
+            Replay::new(Cursor::new(data)),
+            Err(BroodrepError::MalformedSection(_))
+        ));
+    }
+
+    #[test]
+    fn size_breakdown() {
+        let data = TestReplay::new()
+            .modern_section(b"Sbat", vec![0; 32])
+            .modern_section(b"Cust", vec![0; 7])
+            .build();
+        let replay = Replay::new(Cursor::new(data.clone())).unwrap();
+        assert_eq!(replay.file_size(), data.len() as u64);
+
+        let breakdown = replay.size_breakdown();
+        assert_eq!(
+            breakdown.iter().map(|&(s, _)| s).collect::<Vec<_>>(),
+            [
+                ReplaySection::Header,
+                ReplaySection::Commands,
+                ReplaySection::MapData,
+                ReplaySection::PlayerNames,
+                ReplaySection::ShieldBattery,
+                ReplaySection::Custom(SectionTag(*b"Cust")),
+            ]
+        );
+        // size + data + next section's ID
+        assert_eq!(breakdown[4].1, 4 + 32 + 4);
+        // size + data
+        assert_eq!(breakdown[5].1, 4 + 7);
+        assert_eq!(
+            breakdown.iter().map(|&(_, size)| size).sum::<u64>(),
+            replay.file_size() - replay.section_offsets[&ReplaySection::Header]
+        );
+    }
+
+    #[test]
+    fn test_replay_format_legacy() {
+        let mut cursor = Cursor::new(LEGACY);
+        let replay = Replay::new(&mut cursor).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Legacy);
+    }
+
+    #[test]
+    fn test_replay_format_legacy_empty() {
+        let mut cursor = Cursor::new(LEGACY_EMPTY);
+        let replay = Replay::new(&mut cursor).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Legacy);
+    }
+
+    #[test]
+    fn test_replay_format_scr_old() {
+        let mut cursor = Cursor::new(SCR_OLD);
+        let replay = Replay::new(&mut cursor).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Modern);
+    }
+
+    #[test]
+    fn test_replay_format_scr_121() {
+        let mut cursor = Cursor::new(SCR_121);
+        let replay = Replay::new(&mut cursor).unwrap();
+        assert_eq!(replay.format, ReplayFormat::Modern121);
+    }
+
+    /// Parses the commands of a fixture in strict mode, which fails if any command's length is
+    /// unknown or doesn't line up with its frame, so that a successful parse means the whole
+    /// Commands section was understood.
+    fn strict_commands(data: &[u8]) -> Vec<Command> {
+        let config = ParseConfig {
+            commands: CommandParseConfig { strict: true },
+            ..Default::default()
+        };
+        let mut replay = Replay::new_with_config(Cursor::new(data), config).unwrap();
+        let commands = replay.commands().unwrap();
+        assert!(!commands.is_empty());
+        assert!(commands.last().unwrap().frame <= replay.header.frames);
+        commands
+    }
+
+    #[test]
+    fn commands_legacy() {
+        strict_commands(LEGACY);
+    }
+
+    #[test]
+    fn commands_scr_old() {
+        strict_commands(SCR_OLD);
+    }
+
+    #[test]
+    fn commands_scr_121() {
+        strict_commands(SCR_121);
+    }
+
+    #[test]
+    fn commands_sb_data() {
+        strict_commands(SB_DATA);
     }
 
     #[test]
@@ -956,6 +4188,7 @@ mod tests {
                 network_id: 0,
                 player_type: PlayerType::Human,
                 name: "u".into(),
+                raw_name: b"u".to_vec(),
                 race: Race::Terran,
                 team: 1,
             }
@@ -968,6 +4201,7 @@ mod tests {
                 network_id: 255,
                 player_type: PlayerType::Computer,
                 name: "Sargas Tribe".into(),
+                raw_name: b"Sargas Tribe".to_vec(),
                 race: Race::Protoss,
                 team: 1,
             }
@@ -1059,7 +4293,6 @@ mod tests {
         );
     }
 
-    // TODO(tec27): Would be nice to have a test with SB data v0 as well
     #[test]
     fn shieldbattery_section_v1() {
         let mut cursor = Cursor::new(SB_DATA);
@@ -0,0 +1,541 @@
+use std::{collections::HashMap, fmt};
+
+use byteorder::{LittleEndian as LE, ReadBytesExt as _};
+
+use crate::{BroodrepError, StringEncoding, TextEncoding, encoding};
+
+/// Size of a single trigger in the `TRIG` chunk, in bytes.
+const SIZE_TRIGGER: usize = 2400;
+/// Size of a single unit placement in the `UNIT` chunk, in bytes.
+const SIZE_UNIT: usize = 36;
+/// The unit ID of the Start Location marker.
+const UNIT_START_LOCATION: u16 = 214;
+/// Size of the `FORC` chunk, in bytes.
+const SIZE_FORC: usize = 20;
+
+/// Returns an iterator over the chunks in CHK map data, as (tag, data) pairs, in the order they
+/// appear. Chunks that claim to extend past the end of the data are truncated (matching how the
+/// game reads them), and iteration stops at the first chunk with a negative size, as those are
+/// used by map protectors to confuse editors rather than to store data.
+pub(crate) fn chk_chunks(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        let mut tag = [0u8; 4];
+        tag.copy_from_slice(&data[..4]);
+        let size = (&data[4..8]).read_i32::<LE>().ok()?;
+        if size < 0 {
+            return None;
+        }
+        let rest = &data[8..];
+        let (chunk, rest) = rest.split_at((size as usize).min(rest.len()));
+        data = rest;
+        Some((tag, chunk))
+    })
+}
+
+/// Returns the data of the chunk with the specified tag in CHK map data, or [None] if it isn't
+/// present. If there are multiple chunks with the same tag, the last one is returned, as that is
+/// the one the game uses.
+pub(crate) fn find_chk_chunk<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    chk_chunks(data)
+        .filter(|(t, _)| t == tag)
+        .last()
+        .map(|(_, chunk)| chunk)
+}
+
+/// Returns the number of triggers in CHK map data (0 if the map has no `TRIG` chunk).
+pub(crate) fn trigger_count(data: &[u8]) -> usize {
+    find_chk_chunk(data, b"TRIG").map_or(0, |trig| trig.len() / SIZE_TRIGGER)
+}
+
+/// The map a game was played on, parsed from the CHK data stored in a replay's
+/// [MapData](crate::ReplaySection::MapData) section.
+///
+/// CHK data is made up of chunks, each identified by a 4-byte tag (e.g. `VER `, `DIM `, `ERA `,
+/// `STR `). A chunk can appear more than once, in which case the game uses the last one, so only
+/// the last copy of each chunk is kept here.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapData {
+    chunks: HashMap<[u8; 4], Vec<u8>>,
+    string_encoding: StringEncoding,
+    fallback_encodings: Vec<TextEncoding>,
+}
+
+impl MapData {
+    /// Parses CHK map data into its chunks. Returns an error if the data is non-empty but doesn't
+    /// contain any chunks.
+    pub fn parse(bytes: &[u8]) -> Result<MapData, BroodrepError> {
+        Self::parse_with_encodings(bytes, StringEncoding::Auto, &[])
+    }
+
+    /// Like [MapData::parse], but strings from the map's string table will be decoded using
+    /// `string_encoding` and `fallback_encodings` (see
+    /// [ParseConfig::string_encoding](crate::ParseConfig::string_encoding) and
+    /// [ParseConfig::fallback_encodings](crate::ParseConfig::fallback_encodings) for how they
+    /// are used). [Replay::map_data](crate::Replay::map_data) uses the replay's settings.
+    pub fn parse_with_encodings(
+        bytes: &[u8],
+        string_encoding: StringEncoding,
+        fallback_encodings: &[TextEncoding],
+    ) -> Result<MapData, BroodrepError> {
+        let mut chunks = HashMap::new();
+        for (tag, data) in chk_chunks(bytes) {
+            chunks.insert(tag, data.to_vec());
+        }
+        if chunks.is_empty() && !bytes.is_empty() {
+            return Err(BroodrepError::MalformedSection(
+                "map data contains no chunks",
+            ));
+        }
+        Ok(MapData {
+            chunks,
+            string_encoding,
+            fallback_encodings: fallback_encodings.to_vec(),
+        })
+    }
+
+    /// Returns the data of the chunk with the specified tag (e.g. `b"DIM "`), or [None] if the map
+    /// doesn't contain it.
+    pub fn chunk(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        self.chunks.get(tag).map(Vec::as_slice)
+    }
+
+    /// Returns the tags of every chunk in the map, in no particular order.
+    pub fn tags(&self) -> impl Iterator<Item = &[u8; 4]> {
+        self.chunks.keys()
+    }
+
+    /// Returns the string at `index` (1-based, as CHK data refers to them) in the map's string
+    /// table, or [None] if the index is 0 (meaning no string) or the string doesn't exist. The
+    /// extended `STRx` table is used if the map has one, otherwise the `STR ` table is used.
+    ///
+    /// The string is returned as-is, including any formatting codes (e.g. `\x07` to change the
+    /// text color) it contains. Strings are decoded with the encodings the map data was parsed
+    /// with (see [MapData::parse_with_encodings]).
+    pub fn string(&self, index: u32) -> Option<String> {
+        let index = index.checked_sub(1)? as usize;
+        let (table, offset_size) = match self.chunk(b"STRx") {
+            Some(table) => (table, 4),
+            None => (self.chunk(b"STR ")?, 2),
+        };
+        let read = |at: usize| -> Option<usize> {
+            let bytes = table.get(at..at + offset_size)?;
+            Some(if offset_size == 4 {
+                u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+            } else {
+                u16::from_le_bytes(bytes.try_into().unwrap()) as usize
+            })
+        };
+
+        let count = read(0)?;
+        if index >= count {
+            return None;
+        }
+        let start = read(offset_size * (index + 1))?;
+        let data = table.get(start..)?;
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Some(encoding::decode_string(
+            &data[..end],
+            self.string_encoding,
+            &self.fallback_encodings,
+        ))
+    }
+
+    /// Returns the map's name, as set in the map editor, with any formatting codes removed. This
+    /// is usually a cleaner title than [ReplayHeader::map_name](crate::ReplayHeader::map_name),
+    /// which may be truncated. Returns [None] if the map has no `SPRP` chunk or its name string is
+    /// missing.
+    pub fn scenario_name(&self) -> Option<String> {
+        self.scenario_property(0).map(|s| strip_formatting(&s))
+    }
+
+    /// Returns the map's description, as set in the map editor, with any formatting codes removed.
+    /// Line breaks are preserved. Returns [None] if the map has no `SPRP` chunk or its description
+    /// string is missing.
+    pub fn scenario_description(&self) -> Option<String> {
+        self.scenario_property(1).map(|s| strip_formatting(&s))
+    }
+
+    /// Returns the string for one of the string indices in the `SPRP` chunk (0 for the name, 1 for
+    /// the description), including any formatting codes.
+    fn scenario_property(&self, property: usize) -> Option<String> {
+        let sprp = self.chunk(b"SPRP")?;
+        let index = sprp.get(property * 2..property * 2 + 2)?;
+        self.string(u16::from_le_bytes([index[0], index[1]]).into())
+    }
+
+    /// Returns the forces (teams) set up for the map. Only the first 8 player slots can be
+    /// assigned to a force.
+    ///
+    /// Older maps may have a `FORC` chunk that is shorter than 20 bytes, in which case the missing
+    /// bytes are treated as 0 (as the game does), meaning any slots not covered belong to the
+    /// first force, and forces without a name or flags have none set. Maps without a `FORC` chunk
+    /// at all are treated the same way.
+    pub fn forces(&self) -> [Force; 4] {
+        let mut forc = [0u8; SIZE_FORC];
+        if let Some(chunk) = self.chunk(b"FORC") {
+            let len = chunk.len().min(SIZE_FORC);
+            forc[..len].copy_from_slice(&chunk[..len]);
+        }
+        let (slot_forces, rest) = forc.split_at(8);
+        let (names, flags) = rest.split_at(8);
+
+        std::array::from_fn(|i| {
+            let name_index = u16::from_le_bytes([names[i * 2], names[i * 2 + 1]]);
+            let flags = flags[i];
+            Force {
+                name: self.string(name_index.into()).map(|s| strip_formatting(&s)),
+                slots: (0..8)
+                    .filter(|&slot| slot_forces[slot as usize] as usize == i)
+                    .collect(),
+                random_start_locations: flags & 0x01 != 0,
+                allied: flags & 0x02 != 0,
+                allied_victory: flags & 0x04 != 0,
+                shared_vision: flags & 0x08 != 0,
+            }
+        })
+    }
+
+    /// Returns the start locations placed on the map, from its `UNIT` chunk, in the order they
+    /// were placed. Maps without a `UNIT` chunk will return an empty list.
+    pub fn start_locations(&self) -> Vec<StartLocation> {
+        let Some(units) = self.chunk(b"UNIT") else {
+            return Vec::new();
+        };
+        units
+            .chunks_exact(SIZE_UNIT)
+            .filter_map(|mut unit| {
+                let _serial = unit.read_u32::<LE>().ok()?;
+                let x = unit.read_u16::<LE>().ok()?;
+                let y = unit.read_u16::<LE>().ok()?;
+                let unit_id = unit.read_u16::<LE>().ok()?;
+                // Skip the relation type, valid special properties, and valid elements
+                let player = *unit.get(6)?;
+                (unit_id == UNIT_START_LOCATION).then_some(StartLocation { x, y, player })
+            })
+            .collect()
+    }
+
+    /// Returns the map's tileset, from its `ERA ` chunk, or [None] if the chunk is missing.
+    pub fn tileset(&self) -> Option<Tileset> {
+        let era = self.chunk(b"ERA ")?.read_u16::<LE>().ok()?;
+        // The game only looks at the low 3 bits, so every value maps to a valid tileset
+        Some(match era & 0x7 {
+            0 => Tileset::Badlands,
+            1 => Tileset::SpacePlatform,
+            2 => Tileset::Installation,
+            3 => Tileset::Ashworld,
+            4 => Tileset::Jungle,
+            5 => Tileset::Desert,
+            6 => Tileset::Ice,
+            _ => Tileset::Twilight,
+        })
+    }
+}
+
+/// Removes the control characters StarCraft uses as formatting codes in map strings (text colors,
+/// alignment, etc.), keeping line breaks and tabs, and trims the result.
+fn strip_formatting(s: &str) -> String {
+    s.chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// A force (team) on a map. See [MapData::forces].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Force {
+    /// The name of the force, with any formatting codes removed, or [None] if it doesn't have one.
+    pub name: Option<String>,
+    /// The player slots (0-7) assigned to this force, in order.
+    pub slots: Vec<u8>,
+    /// Whether players in this force are placed at random start locations.
+    pub random_start_locations: bool,
+    /// Whether players in this force start allied with each other.
+    pub allied: bool,
+    /// Whether players in this force win together.
+    pub allied_victory: bool,
+    /// Whether players in this force share vision with each other.
+    pub shared_vision: bool,
+}
+
+/// The position of a player's start location on a map. See [MapData::start_locations].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct StartLocation {
+    /// The x coordinate of the center of the start location, in pixels from the left edge of the
+    /// map (there are 32 pixels per tile).
+    pub x: u16,
+    /// The y coordinate of the center of the start location, in pixels from the top edge of the
+    /// map.
+    pub y: u16,
+    /// The player slot (0-11) the start location belongs to. This is the map slot, i.e.
+    /// [Player::slot_id](crate::Player::slot_id).
+    pub player: u8,
+}
+
+/// The tileset (terrain graphics) of a map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Tileset {
+    Badlands,
+    SpacePlatform,
+    Installation,
+    Ashworld,
+    Jungle,
+    Desert,
+    Ice,
+    Twilight,
+}
+
+impl fmt::Display for Tileset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tileset::Badlands => write!(f, "Badlands"),
+            Tileset::SpacePlatform => write!(f, "Space Platform"),
+            Tileset::Installation => write!(f, "Installation"),
+            Tileset::Ashworld => write!(f, "Ashworld"),
+            Tileset::Jungle => write!(f, "Jungle"),
+            Tileset::Desert => write!(f, "Desert"),
+            Tileset::Ice => write!(f, "Ice"),
+            Tileset::Twilight => write!(f, "Twilight"),
+        }
+    }
+}
+
+// NOTE: The checksum the game uses to check that a joining client has the same map as the host
+// (and that ladder map pools are keyed by) is computed over the map *file*, i.e. the MPQ archive
+// the CHK was extracted from. Replays only store the extracted CHK data, so that checksum can't be
+// reproduced from a replay. Matching a replay against a map whitelist needs to go through
+// something that only depends on the CHK contents (see Replay::map_hash) instead.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(tag: &[u8; 4], size: i32, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn chunks() {
+        let data = [
+            chunk(b"VER ", 2, &[0xcd, 0x00]),
+            chunk(b"ERA ", 2, &[1, 0]),
+            chunk(b"ERA ", 2, &[4, 0]),
+            chunk(b"DIM ", 8, &[128, 0]),
+        ]
+        .concat();
+        assert_eq!(chk_chunks(&data).count(), 4);
+        assert_eq!(find_chk_chunk(&data, b"ERA "), Some(&[4u8, 0][..]));
+        assert_eq!(find_chk_chunk(&data, b"DIM "), Some(&[128u8, 0][..]));
+        assert_eq!(find_chk_chunk(&data, b"STR "), None);
+
+        let data = [chunk(b"VER ", 2, &[0xcd, 0x00]), chunk(b"ERA ", -4, &[])].concat();
+        assert_eq!(chk_chunks(&data).count(), 1);
+    }
+
+    #[test]
+    fn map_data() {
+        let data = [
+            chunk(b"VER ", 2, &[0xcd, 0x00]),
+            chunk(b"ERA ", 2, &[1, 0]),
+            chunk(b"ERA ", 2, &[4, 0]),
+        ]
+        .concat();
+        let map = MapData::parse(&data).unwrap();
+        assert_eq!(map.chunk(b"VER "), Some(&[0xcd, 0x00][..]));
+        assert_eq!(map.chunk(b"ERA "), Some(&[4, 0][..]));
+        assert_eq!(map.chunk(b"DIM "), None);
+        assert_eq!(map.tags().count(), 2);
+        assert_eq!(map.tileset(), Some(Tileset::Jungle));
+
+        let map = MapData::parse(&chunk(b"ERA ", 2, &[0x0f, 0])).unwrap();
+        assert_eq!(map.tileset(), Some(Tileset::Twilight));
+        let map = MapData::parse(&chunk(b"VER ", 2, &[0xcd, 0x00])).unwrap();
+        assert_eq!(map.tileset(), None);
+
+        assert!(MapData::parse(&[]).is_ok());
+        assert!(MapData::parse(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn start_locations() {
+        fn unit(x: u16, y: u16, unit_id: u16, player: u8) -> Vec<u8> {
+            let mut data = vec![0; SIZE_UNIT];
+            data[4..6].copy_from_slice(&x.to_le_bytes());
+            data[6..8].copy_from_slice(&y.to_le_bytes());
+            data[8..10].copy_from_slice(&unit_id.to_le_bytes());
+            data[16] = player;
+            data
+        }
+
+        let units = [
+            unit(1040, 272, UNIT_START_LOCATION, 0),
+            unit(600, 600, 176, 11),
+            unit(3056, 3824, UNIT_START_LOCATION, 1),
+        ]
+        .concat();
+        // A trailing partial entry is ignored
+        let mut data = chunk(b"UNIT", units.len() as i32 + 4, &units);
+        data.extend_from_slice(&[0; 4]);
+        let map = MapData::parse(&data).unwrap();
+        assert_eq!(
+            map.start_locations(),
+            [
+                StartLocation {
+                    x: 1040,
+                    y: 272,
+                    player: 0
+                },
+                StartLocation {
+                    x: 3056,
+                    y: 3824,
+                    player: 1
+                },
+            ]
+        );
+        assert_eq!(MapData::default().start_locations(), []);
+    }
+
+    #[test]
+    fn scenario_strings() {
+        fn string_table(strings: &[&[u8]], extended: bool) -> Vec<u8> {
+            let offset_size = if extended { 4 } else { 2 };
+            let mut table = Vec::new();
+            let mut data = Vec::new();
+            let header_size = offset_size * (strings.len() + 1);
+            let push = |table: &mut Vec<u8>, value: usize| {
+                if extended {
+                    table.extend_from_slice(&(value as u32).to_le_bytes());
+                } else {
+                    table.extend_from_slice(&(value as u16).to_le_bytes());
+                }
+            };
+            push(&mut table, strings.len());
+            for s in strings {
+                push(&mut table, header_size + data.len());
+                data.extend_from_slice(s);
+                data.push(0);
+            }
+            table.extend_from_slice(&data);
+            table
+        }
+
+        let strings = string_table(
+            &[
+                b"\x07\xec\xa0\x9c3\xec\x84\xb8\xea\xb3\x84 \x05",
+                b"Line one\r\n\x03Line two",
+            ],
+            false,
+        );
+        let data = [
+            chunk(b"STR ", strings.len() as i32, &strings),
+            chunk(b"SPRP", 4, &[1, 0, 2, 0]),
+        ]
+        .concat();
+        let map = MapData::parse(&data).unwrap();
+        assert_eq!(map.scenario_name().as_deref(), Some("제3세계"));
+        assert_eq!(
+            map.scenario_description().as_deref(),
+            Some("Line one\r\nLine two")
+        );
+        assert_eq!(map.string(1).as_deref(), Some("\u{7}제3세계 \u{5}"));
+        assert_eq!(map.string(0), None);
+        assert_eq!(map.string(3), None);
+
+        // STRx takes precedence
+        let extended = string_table(&[b"Extended"], true);
+        let data = [data, chunk(b"STRx", extended.len() as i32, &extended)].concat();
+        let map = MapData::parse(&data).unwrap();
+        assert_eq!(map.scenario_name().as_deref(), Some("Extended"));
+        assert_eq!(map.scenario_description(), None);
+
+        // "Карта" in Windows-1251
+        let strings = string_table(&[b"\xca\xe0\xf0\xf2\xe0"], false);
+        let data = [
+            chunk(b"STR ", strings.len() as i32, &strings),
+            chunk(b"SPRP", 4, &[1, 0, 0, 0]),
+        ]
+        .concat();
+        let map = MapData::parse_with_encodings(
+            &data,
+            StringEncoding::Auto,
+            &[TextEncoding::Windows1251],
+        )
+        .unwrap();
+        assert_eq!(map.scenario_name().as_deref(), Some("Карта"));
+        let map = MapData::parse_with_encodings(
+            &data,
+            StringEncoding::Utf8Lossy,
+            &[TextEncoding::Windows1251],
+        )
+        .unwrap();
+        assert_eq!(
+            map.scenario_name().as_deref(),
+            Some("\u{fffd}".repeat(5).as_str())
+        );
+
+        assert_eq!(MapData::default().scenario_name(), None);
+    }
+
+    #[test]
+    fn forces() {
+        let strings = [
+            &1u16.to_le_bytes()[..],
+            &4u16.to_le_bytes(),
+            b"Team\x03 A\0",
+        ]
+        .concat();
+        let forc = [
+            &[0, 1, 0, 1, 2, 3, 3, 3][..],
+            &[1, 0, 0, 0, 0, 0, 0, 0],
+            &[0x0f, 0x02],
+        ]
+        .concat();
+        let data = [
+            chunk(b"STR ", strings.len() as i32, &strings),
+            chunk(b"FORC", forc.len() as i32, &forc),
+        ]
+        .concat();
+        let map = MapData::parse(&data).unwrap();
+        let forces = map.forces();
+        assert_eq!(
+            forces[0],
+            Force {
+                name: Some("Team A".into()),
+                slots: vec![0, 2],
+                random_start_locations: true,
+                allied: true,
+                allied_victory: true,
+                shared_vision: true,
+            }
+        );
+        assert_eq!(forces[1].name, None);
+        assert_eq!(forces[1].slots, [1, 3]);
+        assert!(forces[1].allied && !forces[1].shared_vision);
+        // Flags for the last 2 forces are missing, so they default to 0
+        assert_eq!(forces[3].slots, [5, 6, 7]);
+        assert!(!forces[3].allied);
+
+        let forces = MapData::default().forces();
+        assert_eq!(forces[0].slots, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(forces[1].slots.is_empty());
+    }
+
+    #[test]
+    fn triggers() {
+        let data = [
+            chunk(b"VER ", 2, &[0xcd, 0x00]),
+            chunk(b"TRIG", 3 * SIZE_TRIGGER as i32, &[0; 3 * SIZE_TRIGGER]),
+        ]
+        .concat();
+        assert_eq!(trigger_count(&data), 3);
+        assert_eq!(trigger_count(&data[..10]), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sha1::{Digest as _, Sha1};
+
+use crate::analysis::{
+    self, ActionCadence, BuildStep, ChatMessage, ControlGroupStats, EapmConfig, IntensityConfig,
+    LeaveEvent, PlayerSpending, ProductionSample, SpendingConfig, SuspiciousSpan, SyncFrame,
+};
+use crate::commands::{Command, CommandType};
+use crate::shieldbattery::{self, ShieldBatteryData, ShieldBatteryDataError};
+use crate::{
+    BroodrepError, MapData, ReplayFormat, ReplayHeader, ReplaySection, StringEncoding,
+    TextEncoding, TurnRate, map,
+};
+
+/// A replay with all of its sections read, decompressed, and parsed up front, created with
+/// [Replay::resolve](crate::Replay::resolve).
+///
+/// Unlike [Replay](crate::Replay), this doesn't hold onto the reader, so all of its methods take
+/// `&self` and it is [Send] + [Sync]. This makes it easy to share between threads, e.g. to run
+/// several analyses in parallel.
+///
+/// The tradeoff is memory: every section is kept in memory in its decompressed form, along with
+/// the parsed command stream (which is several times larger than the raw Commands section). For a
+/// typical melee replay this is on the order of a few hundred KB, but UMS maps can contain several
+/// MB of map data, and long games can contain hundreds of thousands of commands.
+#[derive(Debug, Clone)]
+pub struct ResolvedReplay {
+    pub format: ReplayFormat,
+    pub header: ReplayHeader,
+    pub(crate) sections: HashMap<ReplaySection, Vec<u8>>,
+    pub(crate) commands: Vec<Command>,
+    pub(crate) string_encoding: StringEncoding,
+    pub(crate) fallback_encodings: Vec<TextEncoding>,
+}
+
+impl ResolvedReplay {
+    /// Returns the (decompressed) bytes of a given replay section, or [None] if it wasn't present
+    /// in the replay file.
+    pub fn get_raw_section(&self, section: ReplaySection) -> Option<&[u8]> {
+        self.sections.get(&section).map(Vec::as_slice)
+    }
+
+    /// Returns the parsed ShieldBattery data section, if present.
+    pub fn get_shieldbattery_section(
+        &self,
+    ) -> Result<Option<ShieldBatteryData>, ShieldBatteryDataError> {
+        self.get_raw_section(ReplaySection::ShieldBattery)
+            .map(shieldbattery::parse_shieldbattery_section)
+            .transpose()
+    }
+
+    /// Returns all of the commands issued by players during the game, in the order they were
+    /// executed. See [Replay::commands](crate::Replay::commands).
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// See [Replay::unit_production_timeline](crate::Replay::unit_production_timeline).
+    pub fn unit_production_timeline(&self, interval: u32) -> HashMap<u8, Vec<ProductionSample>> {
+        analysis::unit_production_timeline(&self.commands, self.header.frames, interval)
+    }
+
+    /// See [Replay::resource_spending](crate::Replay::resource_spending).
+    pub fn resource_spending(&self, config: &SpendingConfig) -> HashMap<u8, PlayerSpending> {
+        analysis::resource_spending(&self.commands, self.header.frames, config)
+    }
+
+    /// See [Replay::detect_splicing](crate::Replay::detect_splicing).
+    pub fn detect_splicing(&self) -> Vec<SuspiciousSpan> {
+        analysis::detect_splicing(&self.commands, self.header.frames)
+    }
+
+    /// See [Replay::selection_sizes](crate::Replay::selection_sizes).
+    pub fn selection_sizes(&self) -> HashMap<u8, BTreeMap<usize, u32>> {
+        analysis::selection_sizes(&self.commands)
+    }
+
+    /// See [Replay::command_stats](crate::Replay::command_stats).
+    pub fn command_stats(&self) -> HashMap<CommandType, usize> {
+        analysis::command_stats(&self.commands)
+    }
+
+    /// See [Replay::commands_by_player](crate::Replay::commands_by_player).
+    pub fn commands_by_player(&self) -> HashMap<u8, Vec<Command>> {
+        analysis::commands_by_player(self.commands.iter().cloned())
+    }
+
+    /// See [Replay::command_stats_by_player](crate::Replay::command_stats_by_player).
+    pub fn command_stats_by_player(&self) -> HashMap<u8, HashMap<CommandType, usize>> {
+        analysis::command_stats_by_player(&self.commands)
+    }
+
+    /// See [Replay::build_orders](crate::Replay::build_orders).
+    pub fn build_orders(&self) -> HashMap<u8, Vec<BuildStep>> {
+        analysis::build_orders(&self.commands, self.header.speed.time_per_step())
+    }
+
+    /// See [Replay::chat_messages](crate::Replay::chat_messages).
+    pub fn chat_messages(&self) -> Vec<ChatMessage> {
+        analysis::chat_messages(&self.commands, self.header.speed.time_per_step())
+    }
+
+    /// See [Replay::turn_rate](crate::Replay::turn_rate).
+    pub fn turn_rate(&self) -> Option<TurnRate> {
+        analysis::turn_rate(&self.commands)
+    }
+
+    /// See [Replay::action_cadence](crate::Replay::action_cadence).
+    pub fn action_cadence(&self) -> HashMap<u8, ActionCadence> {
+        analysis::action_cadence(&self.commands)
+    }
+
+    /// See [Replay::control_group_stats](crate::Replay::control_group_stats).
+    pub fn control_group_stats(&self) -> HashMap<u8, ControlGroupStats> {
+        analysis::control_group_stats(&self.commands)
+    }
+
+    /// See [Replay::apm](crate::Replay::apm).
+    pub fn apm(&self) -> HashMap<u8, f64> {
+        analysis::apm(
+            &self.commands,
+            self.header.speed.time_per_step() * self.header.frames,
+        )
+    }
+
+    /// See [Replay::eapm](crate::Replay::eapm).
+    pub fn eapm(&self, config: &EapmConfig) -> HashMap<u8, f64> {
+        analysis::eapm(
+            &self.commands,
+            self.header.speed.time_per_step() * self.header.frames,
+            config,
+        )
+    }
+
+    /// See [Replay::leave_events](crate::Replay::leave_events).
+    pub fn leave_events(&self) -> Vec<LeaveEvent> {
+        analysis::leave_events(&self.commands)
+    }
+
+    /// See [Replay::sync_frames](crate::Replay::sync_frames).
+    pub fn sync_frames(&self) -> Vec<SyncFrame> {
+        analysis::sync_frames(&self.commands)
+    }
+
+    /// See [Replay::inferred_winner](crate::Replay::inferred_winner).
+    pub fn inferred_winner(&self) -> Option<u8> {
+        let players = self.header.opposing_pair()?;
+        analysis::inferred_winner(&self.leave_events(), players)
+    }
+
+    /// See [Replay::intensity_score](crate::Replay::intensity_score).
+    pub fn intensity_score(&self, config: &IntensityConfig) -> f64 {
+        analysis::intensity_score(
+            &self.commands,
+            self.header.frames,
+            self.header.speed.time_per_step(),
+            config,
+        )
+    }
+
+    /// See [Replay::map_data](crate::Replay::map_data).
+    pub fn map_data(&self) -> Result<Option<MapData>, BroodrepError> {
+        self.get_raw_section(ReplaySection::MapData)
+            .map(|data| {
+                MapData::parse_with_encodings(data, self.string_encoding, &self.fallback_encodings)
+            })
+            .transpose()
+    }
+
+    /// See [Replay::map_hash](crate::Replay::map_hash).
+    pub fn map_hash(&self) -> Option<[u8; 20]> {
+        self.get_raw_section(ReplaySection::MapData)
+            .map(|data| Sha1::digest(data).into())
+    }
+
+    /// See [Replay::trigger_count](crate::Replay::trigger_count).
+    pub fn trigger_count(&self) -> Option<usize> {
+        self.get_raw_section(ReplaySection::MapData)
+            .map(map::trigger_count)
+    }
+}
@@ -14,10 +14,36 @@ pub enum ShieldBatteryDataError {
     IoError(#[from] std::io::Error),
     #[error("invalid string: {0}")]
     InvalidString(#[from] FromBytesUntilNulError),
+    #[error("invalid length for version {version} data: expected {expected} bytes, got {actual}")]
+    InvalidLength {
+        version: u16,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("section is truncated: expected at least {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
 }
 
+/// Size of a version 0 ShieldBattery section, in bytes (including the version field).
+const SIZE_V0: usize = 0x56;
+/// Minimum size of a version 1+ ShieldBattery section, in bytes (including the version field).
+const MIN_SIZE_V1: usize = SIZE_V0 + 2;
+
+/// Extra data about a game written to replays by ShieldBattery.
+///
+/// NOTE: This does not include the game's result. ShieldBattery reconciles results from every
+/// player's client on the server after the game has completed, so no version of this section has
+/// ever contained them, and there are no trailing bytes to recover them from. Results have to be
+/// inferred from the command stream instead (or retrieved from ShieldBattery itself, via
+/// [game_id](ShieldBatteryData::game_id)). The same is true of the server region and matchmaking
+/// type, which are tracked by ShieldBattery but not written into replays.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShieldBatteryData {
+    /// The version of the section's format. Versions newer than those broodrep knows about are
+    /// parsed as the newest known version, with any additional data left in
+    /// [raw_tail](ShieldBatteryData::raw_tail).
+    pub section_version: u16,
     /// The build number of the StarCraft executable used to play the game.
     pub starcraft_exe_build: u32,
     /// The version string of the ShieldBattery client used to play the game.
@@ -34,12 +60,52 @@ pub struct ShieldBatteryData {
     /// The version of ShieldBattery game logic modifications used to play the game. May not be
     /// present on older replays.
     pub game_logic_version: Option<u16>,
+    /// Any bytes following the fields broodrep knows how to parse. This will be empty for all
+    /// currently known versions of the section, but allows data added by newer versions to be
+    /// accessed without an update to broodrep.
+    pub raw_tail: Vec<u8>,
+}
+
+impl ShieldBatteryData {
+    /// Returns the ShieldBattery user ID of the player in the header slot at `slot_index` (i.e. the
+    /// index into [ReplayHeader::slots](crate::ReplayHeader::slots), not
+    /// [Player::slot_id](crate::Player::slot_id)), or [None] if the slot wasn't occupied by a
+    /// ShieldBattery user. Only the first 8 slots have user IDs, so this will always be [None] for
+    /// observers in later slots.
+    ///
+    /// See [Replay::all_participants](crate::Replay::all_participants) to get the user IDs along
+    /// with the players they belong to.
+    pub fn user_id_for_slot(&self, slot_index: usize) -> Option<u32> {
+        self.user_ids.get(slot_index).copied().filter(|&id| id != 0)
+    }
 }
 
 pub fn parse_shieldbattery_section(
     mut data: &[u8],
 ) -> Result<ShieldBatteryData, ShieldBatteryDataError> {
+    let total_len = data.len();
+    if total_len < 2 {
+        return Err(ShieldBatteryDataError::Truncated {
+            expected: 2,
+            actual: total_len,
+        });
+    }
     let version = data.read_u16::<LE>()?;
+    if version == 0 && total_len != SIZE_V0 {
+        // v0 sections have no optional fields, so anything other than the exact size means we'd
+        // either be reading garbage or dropping data
+        return Err(ShieldBatteryDataError::InvalidLength {
+            version,
+            expected: SIZE_V0,
+            actual: total_len,
+        });
+    }
+    if version >= 1 && total_len < MIN_SIZE_V1 {
+        return Err(ShieldBatteryDataError::Truncated {
+            expected: MIN_SIZE_V1,
+            actual: total_len,
+        });
+    }
 
     let starcraft_exe_build = data.read_u32::<LE>()?;
     let mut shieldbattery_version = [0; 0x11];
@@ -57,6 +123,7 @@ pub fn parse_shieldbattery_section(
     data.read_u32_into::<LE>(&mut user_ids)?;
 
     let mut parsed = ShieldBatteryData {
+        section_version: version,
         starcraft_exe_build,
         shieldbattery_version,
         team_game_main_players,
@@ -64,10 +131,145 @@ pub fn parse_shieldbattery_section(
         game_id,
         user_ids,
         game_logic_version: None,
+        raw_tail: Vec::new(),
     };
     if version >= 1 {
         parsed.game_logic_version = Some(data.read_u16::<LE>()?);
     }
+    parsed.raw_tail = data.to_vec();
 
     Ok(parsed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v0_section() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&13515u32.to_le_bytes());
+        let mut version = [0u8; 0x10];
+        version[..6].copy_from_slice(b"8.0.12");
+        data.extend_from_slice(&version);
+        data.extend_from_slice(&[1, 0, 0, 0]);
+        data.extend_from_slice(&[2, 1, 0, 6, 6, 6, 6, 6, 6, 6, 6, 6]);
+        data.extend_from_slice(&0x0123_4567_89ab_cdef_0011_2233_4455_6677u128.to_le_bytes());
+        for id in [5u32, 7, 0, 0, 0, 0, 0, 0] {
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn v0_section_parses() {
+        let data = v0_section();
+        assert_eq!(data.len(), SIZE_V0);
+
+        let parsed = parse_shieldbattery_section(&data).unwrap();
+        assert_eq!(parsed.section_version, 0);
+        assert_eq!(parsed.starcraft_exe_build, 13515);
+        assert_eq!(parsed.shieldbattery_version, "8.0.12");
+        assert_eq!(parsed.team_game_main_players, [1, 0, 0, 0]);
+        assert_eq!(
+            parsed.starting_races,
+            [
+                Race::Protoss,
+                Race::Terran,
+                Race::Zerg,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+                Race::Random,
+            ]
+        );
+        assert_eq!(parsed.game_id, 0x0123_4567_89ab_cdef_0011_2233_4455_6677);
+        assert_eq!(parsed.user_ids, [5, 7, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(parsed.game_logic_version, None);
+    }
+
+    #[test]
+    fn user_id_for_slot() {
+        let parsed = parse_shieldbattery_section(&v0_section()).unwrap();
+        assert_eq!(parsed.user_id_for_slot(0), Some(5));
+        assert_eq!(parsed.user_id_for_slot(1), Some(7));
+        assert_eq!(parsed.user_id_for_slot(2), None);
+        assert_eq!(parsed.user_id_for_slot(8), None);
+        assert_eq!(parsed.user_id_for_slot(usize::MAX), None);
+    }
+
+    #[test]
+    fn v0_section_wrong_length() {
+        let mut data = v0_section();
+        data.extend_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(
+            parse_shieldbattery_section(&data),
+            Err(ShieldBatteryDataError::InvalidLength {
+                version: 0,
+                expected: SIZE_V0,
+                actual: 0x58,
+            })
+        ));
+
+        let data = &v0_section()[..0x50];
+        assert!(matches!(
+            parse_shieldbattery_section(data),
+            Err(ShieldBatteryDataError::InvalidLength {
+                version: 0,
+                expected: SIZE_V0,
+                actual: 0x50,
+            })
+        ));
+    }
+
+    #[test]
+    fn v1_section_parses() {
+        let mut data = v0_section();
+        data[0] = 1;
+        data.extend_from_slice(&3u16.to_le_bytes());
+
+        let parsed = parse_shieldbattery_section(&data).unwrap();
+        assert_eq!(parsed.starcraft_exe_build, 13515);
+        assert_eq!(parsed.user_ids, [5, 7, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(parsed.game_logic_version, Some(3));
+        assert!(parsed.raw_tail.is_empty());
+    }
+
+    #[test]
+    fn truncated_section() {
+        let mut data = v0_section();
+        data[0] = 1;
+        assert!(matches!(
+            parse_shieldbattery_section(&data),
+            Err(ShieldBatteryDataError::Truncated {
+                expected: MIN_SIZE_V1,
+                actual: SIZE_V0,
+            })
+        ));
+        assert!(matches!(
+            parse_shieldbattery_section(&data[..1]),
+            Err(ShieldBatteryDataError::Truncated {
+                expected: 2,
+                actual: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn newer_section_keeps_tail() {
+        let mut data = v0_section();
+        data[0] = 2;
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&[9, 8, 7]);
+
+        let parsed = parse_shieldbattery_section(&data).unwrap();
+        assert_eq!(parsed.section_version, 2);
+        assert_eq!(parsed.game_logic_version, Some(3));
+        assert_eq!(parsed.raw_tail, [9, 8, 7]);
+    }
+}
@@ -0,0 +1,477 @@
+/// Names for each of the unit types in Brood War, indexed by unit ID.
+const UNIT_NAMES: [&str; 228] = [
+    "Marine",
+    "Ghost",
+    "Vulture",
+    "Goliath",
+    "Goliath Turret",
+    "Siege Tank (Tank Mode)",
+    "Siege Tank Turret (Tank Mode)",
+    "SCV",
+    "Wraith",
+    "Science Vessel",
+    "Gui Montag",
+    "Dropship",
+    "Battlecruiser",
+    "Spider Mine",
+    "Nuclear Missile",
+    "Civilian",
+    "Sarah Kerrigan",
+    "Alan Schezar",
+    "Alan Schezar Turret",
+    "Jim Raynor (Vulture)",
+    "Jim Raynor (Marine)",
+    "Tom Kazansky",
+    "Magellan",
+    "Edmund Duke (Tank Mode)",
+    "Edmund Duke Turret (Tank Mode)",
+    "Edmund Duke (Siege Mode)",
+    "Edmund Duke Turret (Siege Mode)",
+    "Arcturus Mengsk",
+    "Hyperion",
+    "Norad II",
+    "Siege Tank (Siege Mode)",
+    "Siege Tank Turret (Siege Mode)",
+    "Firebat",
+    "Scanner Sweep",
+    "Medic",
+    "Larva",
+    "Egg",
+    "Zergling",
+    "Hydralisk",
+    "Ultralisk",
+    "Broodling",
+    "Drone",
+    "Overlord",
+    "Mutalisk",
+    "Guardian",
+    "Queen",
+    "Defiler",
+    "Scourge",
+    "Torrasque",
+    "Matriarch",
+    "Infested Terran",
+    "Infested Kerrigan",
+    "Unclean One",
+    "Hunter Killer",
+    "Devouring One",
+    "Kukulza (Mutalisk)",
+    "Kukulza (Guardian)",
+    "Yggdrasill",
+    "Valkyrie",
+    "Cocoon",
+    "Corsair",
+    "Dark Templar",
+    "Devourer",
+    "Dark Archon",
+    "Probe",
+    "Zealot",
+    "Dragoon",
+    "High Templar",
+    "Archon",
+    "Shuttle",
+    "Scout",
+    "Arbiter",
+    "Carrier",
+    "Interceptor",
+    "Dark Templar (Hero)",
+    "Zeratul",
+    "Tassadar/Zeratul (Archon)",
+    "Fenix (Zealot)",
+    "Fenix (Dragoon)",
+    "Tassadar",
+    "Mojo",
+    "Warbringer",
+    "Gantrithor",
+    "Reaver",
+    "Observer",
+    "Scarab",
+    "Danimoth",
+    "Aldaris",
+    "Artanis",
+    "Rhynadon",
+    "Bengalaas",
+    "Cargo Ship",
+    "Mercenary Gunship",
+    "Scantid",
+    "Kakaru",
+    "Ragnasaur",
+    "Ursadon",
+    "Lurker Egg",
+    "Raszagal",
+    "Samir Duran",
+    "Alexei Stukov",
+    "Map Revealer",
+    "Gerard DuGalle",
+    "Lurker",
+    "Infested Duran",
+    "Disruption Web",
+    "Command Center",
+    "Comsat Station",
+    "Nuclear Silo",
+    "Supply Depot",
+    "Refinery",
+    "Barracks",
+    "Academy",
+    "Factory",
+    "Starport",
+    "Control Tower",
+    "Science Facility",
+    "Covert Ops",
+    "Physics Lab",
+    "Starbase",
+    "Machine Shop",
+    "Repair Bay",
+    "Engineering Bay",
+    "Armory",
+    "Missile Turret",
+    "Bunker",
+    "Crashed Norad II",
+    "Ion Cannon",
+    "Uraj Crystal",
+    "Khalis Crystal",
+    "Infested Command Center",
+    "Hatchery",
+    "Lair",
+    "Hive",
+    "Nydus Canal",
+    "Hydralisk Den",
+    "Defiler Mound",
+    "Greater Spire",
+    "Queen's Nest",
+    "Evolution Chamber",
+    "Ultralisk Cavern",
+    "Spire",
+    "Spawning Pool",
+    "Creep Colony",
+    "Spore Colony",
+    "Unused Zerg Building",
+    "Sunken Colony",
+    "Overmind (With Shell)",
+    "Overmind",
+    "Extractor",
+    "Mature Chrysalis",
+    "Cerebrate",
+    "Cerebrate Daggoth",
+    "Unused Zerg Building 2",
+    "Nexus",
+    "Robotics Facility",
+    "Pylon",
+    "Assimilator",
+    "Unused Protoss Building",
+    "Observatory",
+    "Gateway",
+    "Unused Protoss Building 2",
+    "Photon Cannon",
+    "Citadel of Adun",
+    "Cybernetics Core",
+    "Templar Archives",
+    "Forge",
+    "Stargate",
+    "Stasis Cell/Prison",
+    "Fleet Beacon",
+    "Arbiter Tribunal",
+    "Robotics Support Bay",
+    "Shield Battery",
+    "Khaydarin Crystal Formation",
+    "Protoss Temple",
+    "Xel'Naga Temple",
+    "Mineral Field (Type 1)",
+    "Mineral Field (Type 2)",
+    "Mineral Field (Type 3)",
+    "Cave",
+    "Cave-in",
+    "Cantina",
+    "Mining Platform",
+    "Independent Command Center",
+    "Independent Starport",
+    "Independent Jump Gate",
+    "Ruins",
+    "Khaydarin Crystal Formation (Unused)",
+    "Vespene Geyser",
+    "Warp Gate",
+    "Psi Disrupter",
+    "Zerg Marker",
+    "Terran Marker",
+    "Protoss Marker",
+    "Zerg Beacon",
+    "Terran Beacon",
+    "Protoss Beacon",
+    "Zerg Flag Beacon",
+    "Terran Flag Beacon",
+    "Protoss Flag Beacon",
+    "Power Generator",
+    "Overmind Cocoon",
+    "Dark Swarm",
+    "Floor Missile Trap",
+    "Floor Hatch",
+    "Left Upper Level Door",
+    "Right Upper Level Door",
+    "Left Pit Door",
+    "Right Pit Door",
+    "Floor Gun Trap",
+    "Left Wall Missile Trap",
+    "Left Wall Flame Trap",
+    "Right Wall Missile Trap",
+    "Right Wall Flame Trap",
+    "Start Location",
+    "Flag",
+    "Young Chrysalis",
+    "Psi Emitter",
+    "Data Disc",
+    "Khaydarin Crystal",
+    "Mineral Cluster (Type 1)",
+    "Mineral Cluster (Type 2)",
+    "Protoss Vespene Gas Orb (Type 1)",
+    "Protoss Vespene Gas Orb (Type 2)",
+    "Zerg Vespene Gas Sac (Type 1)",
+    "Zerg Vespene Gas Sac (Type 2)",
+    "Terran Vespene Gas Tank (Type 1)",
+    "Terran Vespene Gas Tank (Type 2)",
+];
+
+/// The unit ID used by the game to mean "no unit" (e.g. for right clicks on the ground).
+pub const UNIT_NONE: u16 = 228;
+
+/// Returns the name of the Brood War unit type with the specified ID, or [None] if it isn't a
+/// valid unit type.
+pub fn unit_name(unit_type: u16) -> Option<&'static str> {
+    UNIT_NAMES.get(unit_type as usize).copied()
+}
+
+/// An amount of resources.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Cost {
+    pub minerals: u32,
+    pub gas: u32,
+}
+
+impl Cost {
+    const fn new(minerals: u32, gas: u32) -> Self {
+        Self { minerals, gas }
+    }
+}
+
+impl std::ops::Add for Cost {
+    type Output = Cost;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Cost::new(self.minerals + rhs.minerals, self.gas + rhs.gas)
+    }
+}
+
+impl std::ops::AddAssign for Cost {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// Returns the cost of producing a unit of the specified type, or [None] if it isn't a type that
+/// can be produced by players. For Zerglings and Scourge, this is the cost of a pair (as a single
+/// larva morphs into 2 units). For units morphed from other units (e.g. Lurkers, Lair), this only
+/// includes the additional cost of the morph.
+pub fn unit_cost(unit_type: u16) -> Option<Cost> {
+    let cost = match unit_type {
+        // Terran units
+        0 => Cost::new(50, 0),     // Marine
+        1 => Cost::new(25, 75),    // Ghost
+        2 => Cost::new(75, 0),     // Vulture
+        3 => Cost::new(100, 50),   // Goliath
+        5 => Cost::new(150, 100),  // Siege Tank
+        7 => Cost::new(50, 0),     // SCV
+        8 => Cost::new(150, 100),  // Wraith
+        9 => Cost::new(100, 225),  // Science Vessel
+        11 => Cost::new(100, 100), // Dropship
+        12 => Cost::new(400, 300), // Battlecruiser
+        14 => Cost::new(200, 200), // Nuclear Missile
+        32 => Cost::new(50, 25),   // Firebat
+        34 => Cost::new(50, 25),   // Medic
+        58 => Cost::new(250, 125), // Valkyrie
+        // Zerg units
+        37 => Cost::new(50, 0),    // Zergling
+        38 => Cost::new(75, 25),   // Hydralisk
+        39 => Cost::new(200, 200), // Ultralisk
+        41 => Cost::new(50, 0),    // Drone
+        42 => Cost::new(100, 0),   // Overlord
+        43 => Cost::new(100, 100), // Mutalisk
+        44 => Cost::new(50, 100),  // Guardian
+        45 => Cost::new(100, 100), // Queen
+        46 => Cost::new(50, 150),  // Defiler
+        47 => Cost::new(25, 75),   // Scourge
+        50 => Cost::new(100, 50),  // Infested Terran
+        62 => Cost::new(150, 50),  // Devourer
+        103 => Cost::new(50, 100), // Lurker
+        // Protoss units
+        60 => Cost::new(150, 100), // Corsair
+        61 => Cost::new(125, 100), // Dark Templar
+        64 => Cost::new(50, 0),    // Probe
+        65 => Cost::new(100, 0),   // Zealot
+        66 => Cost::new(125, 50),  // Dragoon
+        67 => Cost::new(50, 150),  // High Templar
+        69 => Cost::new(200, 0),   // Shuttle
+        70 => Cost::new(275, 125), // Scout
+        71 => Cost::new(100, 350), // Arbiter
+        72 => Cost::new(350, 250), // Carrier
+        73 => Cost::new(25, 0),    // Interceptor
+        83 => Cost::new(200, 100), // Reaver
+        84 => Cost::new(25, 75),   // Observer
+        85 => Cost::new(15, 0),    // Scarab
+        // Terran buildings
+        106 => Cost::new(400, 0),   // Command Center
+        107 => Cost::new(50, 50),   // Comsat Station
+        108 => Cost::new(100, 100), // Nuclear Silo
+        109 => Cost::new(100, 0),   // Supply Depot
+        110 => Cost::new(100, 0),   // Refinery
+        111 => Cost::new(150, 0),   // Barracks
+        112 => Cost::new(150, 0),   // Academy
+        113 => Cost::new(200, 100), // Factory
+        114 => Cost::new(150, 100), // Starport
+        115 => Cost::new(50, 50),   // Control Tower
+        116 => Cost::new(100, 150), // Science Facility
+        117 => Cost::new(50, 50),   // Covert Ops
+        118 => Cost::new(50, 50),   // Physics Lab
+        120 => Cost::new(50, 50),   // Machine Shop
+        122 => Cost::new(125, 0),   // Engineering Bay
+        123 => Cost::new(100, 50),  // Armory
+        124 => Cost::new(75, 0),    // Missile Turret
+        125 => Cost::new(100, 0),   // Bunker
+        // Zerg buildings
+        131 => Cost::new(300, 0),   // Hatchery
+        132 => Cost::new(150, 100), // Lair
+        133 => Cost::new(200, 150), // Hive
+        134 => Cost::new(150, 0),   // Nydus Canal
+        135 => Cost::new(100, 50),  // Hydralisk Den
+        136 => Cost::new(100, 100), // Defiler Mound
+        137 => Cost::new(100, 150), // Greater Spire
+        138 => Cost::new(150, 100), // Queen's Nest
+        139 => Cost::new(75, 0),    // Evolution Chamber
+        140 => Cost::new(150, 200), // Ultralisk Cavern
+        141 => Cost::new(200, 150), // Spire
+        142 => Cost::new(200, 0),   // Spawning Pool
+        143 => Cost::new(75, 0),    // Creep Colony
+        144 => Cost::new(50, 0),    // Spore Colony
+        146 => Cost::new(50, 0),    // Sunken Colony
+        149 => Cost::new(50, 0),    // Extractor
+        // Protoss buildings
+        154 => Cost::new(400, 0),   // Nexus
+        155 => Cost::new(200, 200), // Robotics Facility
+        156 => Cost::new(100, 0),   // Pylon
+        157 => Cost::new(100, 0),   // Assimilator
+        159 => Cost::new(50, 100),  // Observatory
+        160 => Cost::new(150, 0),   // Gateway
+        162 => Cost::new(150, 0),   // Photon Cannon
+        163 => Cost::new(150, 100), // Citadel of Adun
+        164 => Cost::new(200, 0),   // Cybernetics Core
+        165 => Cost::new(150, 200), // Templar Archives
+        166 => Cost::new(150, 0),   // Forge
+        167 => Cost::new(150, 150), // Stargate
+        169 => Cost::new(300, 200), // Fleet Beacon
+        170 => Cost::new(200, 150), // Arbiter Tribunal
+        171 => Cost::new(150, 100), // Robotics Support Bay
+        172 => Cost::new(100, 0),   // Shield Battery
+        _ => return None,
+    };
+
+    Some(cost)
+}
+
+/// Returns the supply used by producing a unit of the specified type. As with [unit_cost], this
+/// is for a pair of Zerglings or Scourge, and only includes the additional supply used for units
+/// morphed from other units. Buildings, and any other units that don't use supply, return 0.
+///
+/// Supply is given in whole units as displayed in game (the game tracks it internally in halves,
+/// which is why Zerglings and Scourge only use 0.5 each).
+pub fn unit_supply(unit_type: u16) -> u32 {
+    match unit_type {
+        // Marine, SCV, Firebat, Medic, Ghost
+        0 | 7 | 32 | 34 | 1 => 1,
+        // Zergling (pair), Hydralisk, Drone, Scourge (pair), Infested Terran, Lurker (from Hydralisk)
+        37 | 38 | 41 | 47 | 50 | 103 => 1,
+        // Probe, Observer
+        64 | 84 => 1,
+        // Vulture, Goliath, Siege Tank, Wraith, Science Vessel, Dropship
+        2 | 3 | 5 | 8 | 9 | 11 => 2,
+        // Mutalisk, Queen, Defiler
+        43 | 45 | 46 => 2,
+        // Corsair, Dark Templar, Zealot, Dragoon, High Templar, Shuttle
+        60 | 61 | 65 | 66 | 67 | 69 => 2,
+        // Valkyrie, Scout
+        58 | 70 => 3,
+        // Ultralisk, Arbiter, Reaver
+        39 | 71 | 83 => 4,
+        // Battlecruiser, Carrier
+        12 | 72 => 6,
+        // Nuclear Missile
+        14 => 8,
+        _ => 0,
+    }
+}
+
+/// Returns the cost of researching the tech with the specified ID, or [None] if it isn't a tech
+/// that can be researched.
+pub fn tech_cost(tech: u8) -> Option<Cost> {
+    let cost = match tech {
+        0 | 3 | 8 | 10 | 11 | 13 | 16 | 17 | 24 | 30 | 31 => Cost::new(100, 100),
+        5 | 9 | 20 | 21 | 22 => Cost::new(150, 150),
+        1 | 2 | 7 | 15 | 19 | 25 | 27 | 32 => Cost::new(200, 200),
+        _ => return None,
+    };
+
+    Some(cost)
+}
+
+/// Returns the cost of researching the specified level (starting at 1) of the upgrade with the
+/// specified ID, or [None] if it isn't an upgrade that can be researched.
+pub fn upgrade_cost(upgrade: u8, level: u8) -> Option<Cost> {
+    // (base cost, additional cost per level), minerals and gas are always the same
+    let (base, factor) = match upgrade {
+        // Armor/weapon upgrades
+        0 | 1 | 5 | 7 | 8 => (100, 75),
+        2 | 3 | 4 | 6 => (150, 75),
+        9 | 10 | 11 | 13 => (100, 50),
+        12 | 14 => (100, 75),
+        15 => (200, 100),
+        // Single level upgrades
+        17 | 20 | 27 | 41 | 43 | 47 | 52 => (100, 0),
+        16 | 19 | 21 | 23 | 25 | 26 | 29 | 30 | 31 | 32 | 33 | 34 | 38 | 39 | 40 | 44 | 49 | 51 => {
+            (150, 0)
+        }
+        22 | 24 | 28 | 35 | 36 | 37 | 42 => (200, 0),
+        _ => return None,
+    };
+    let amount = base + factor * (level.max(1) as u32 - 1);
+
+    Some(Cost::new(amount, amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(unit_name(0), Some("Marine"));
+        assert_eq!(unit_name(37), Some("Zergling"));
+        assert_eq!(unit_name(103), Some("Lurker"));
+        assert_eq!(unit_name(142), Some("Spawning Pool"));
+        assert_eq!(unit_name(160), Some("Gateway"));
+        assert_eq!(unit_name(227), Some("Terran Vespene Gas Tank (Type 2)"));
+        assert_eq!(unit_name(UNIT_NONE), None);
+    }
+
+    #[test]
+    fn costs() {
+        assert_eq!(unit_cost(0), Some(Cost::new(50, 0)));
+        assert_eq!(unit_cost(142), Some(Cost::new(200, 0)));
+        assert_eq!(unit_cost(176), None);
+        assert_eq!(tech_cost(0), Some(Cost::new(100, 100)));
+        assert_eq!(tech_cost(4), None);
+        assert_eq!(upgrade_cost(3, 1), Some(Cost::new(150, 150)));
+        assert_eq!(upgrade_cost(3, 3), Some(Cost::new(300, 300)));
+        assert_eq!(upgrade_cost(27, 1), Some(Cost::new(100, 100)));
+        assert_eq!(upgrade_cost(18, 1), None);
+        assert_eq!(unit_supply(37), 1);
+        assert_eq!(unit_supply(12), 6);
+        assert_eq!(unit_supply(142), 0);
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers for identifying the version of StarCraft a replay was recorded with.
+
+/// Known StarCraft: Remastered executable build numbers, and the patch they belong to. The build
+/// number is the last component of `StarCraft.exe`'s file version (e.g. `1.23.10.13515`), so each
+/// row should come from the version resource of the executable shipped with that patch, noted
+/// next to the row.
+const BUILDS: &[(u32, &str)] = &[
+    // StarCraft.exe file version 1.23.10.13515
+    (13515, "1.23.10"),
+];
+
+/// Returns the StarCraft: Remastered patch (e.g. `"1.23.10"`) that the executable with the
+/// specified build number belongs to, or [None] if the build isn't known. Build numbers can be
+/// found in [ShieldBatteryData::starcraft_exe_build](crate::ShieldBatteryData::starcraft_exe_build).
+pub fn build_to_version(build: u32) -> Option<&'static str> {
+    BUILDS
+        .iter()
+        .find(|&&(b, _)| b == build)
+        .map(|&(_, version)| version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_builds() {
+        assert_eq!(build_to_version(13515), Some("1.23.10"));
+        assert_eq!(build_to_version(0), None);
+    }
+}